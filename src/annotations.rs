@@ -0,0 +1,224 @@
+//! Labelled points and regions on the sphere, kept in a JSON sidecar next to
+//! the image, e.g. `bridge.jpg.annotations.json`:
+//!
+//! ```json
+//! {
+//!   "annotations": [
+//!     { "label": "Crack", "kind": "point", "at": { "yaw": 12.5, "pitch": -3.0 } },
+//!     { "label": "Rust", "kind": "region", "corners": [
+//!       { "yaw": 40.0, "pitch": 5.0 },
+//!       { "yaw": 52.0, "pitch": 5.0 },
+//!       { "yaw": 46.0, "pitch": -4.0 }
+//!     ] }
+//!   ]
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use ultraviolet::Vec3;
+
+use crate::sidecar;
+
+/// A direction on the sphere in degrees, as shown by the HUD: yaw is the
+/// longitude and pitch the latitude of the overview.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Direction {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl Direction {
+    pub fn from_vector(v: Vec3) -> Self {
+        let v = v.normalized();
+        Self {
+            yaw: (-v.z).atan2(-v.x).to_degrees(),
+            pitch: v.y.clamp(-1.0, 1.0).asin().to_degrees(),
+        }
+    }
+
+    pub fn to_vector(self) -> Vec3 {
+        let (lon, lat) = (self.yaw.to_radians(), self.pitch.to_radians());
+        Vec3::new(-lat.cos() * lon.cos(), lat.sin(), -lat.cos() * lon.sin())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Shape {
+    Point {
+        at: Direction,
+    },
+    /// A polygon whose edges follow great circles between the corners.
+    Region {
+        corners: Vec<Direction>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Annotation {
+    pub label: String,
+    #[serde(flatten)]
+    pub shape: Shape,
+}
+
+impl Annotation {
+    /// Gets where the label goes: the point itself, or the middle of a region.
+    pub fn anchor(&self) -> Vec3 {
+        match &self.shape {
+            Shape::Point { at } => at.to_vector(),
+            Shape::Region { corners } => corners
+                .iter()
+                .map(|c| c.to_vector())
+                .fold(Vec3::zero(), |sum, v| sum + v)
+                .normalized(),
+        }
+    }
+
+    /// Gets the smallest angle in radians between `direction` and the
+    /// annotation's point or any of its corners.
+    fn angle_to(&self, direction: Vec3) -> f32 {
+        let angle = |at: &Direction| at.to_vector().dot(direction).clamp(-1.0, 1.0).acos();
+        match &self.shape {
+            Shape::Point { at } => angle(at),
+            Shape::Region { corners } => corners.iter().map(angle).fold(f32::MAX, f32::min),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Sidecar {
+    annotations: Vec<Annotation>,
+}
+
+/// A label being typed.
+pub struct Editing {
+    /// Index into [`Annotations::items`].
+    pub index: usize,
+    /// The label before editing began, or `None` for a new annotation that
+    /// cancelling removes.
+    original: Option<String>,
+}
+
+/// The annotations of the image on screen, along with any being drawn or
+/// labelled.
+pub struct Annotations {
+    pub items: Vec<Annotation>,
+    path: PathBuf,
+    /// Corners of a region being drawn, not yet part of `items`.
+    pub pending_region: Vec<Direction>,
+    pub editing: Option<Editing>,
+}
+
+impl Annotations {
+    /// Reads an image's sidecar.
+    pub fn load(image: &Path) -> Self {
+        let path = sidecar::path(image, "annotations");
+        let sidecar: Sidecar = sidecar::load(&path);
+        Self {
+            items: sidecar.annotations,
+            path,
+            pending_region: Vec::new(),
+            editing: None,
+        }
+    }
+
+    /// Writes the sidecar, unless there's nothing to write and never was.
+    pub fn save(&self) {
+        if self.items.is_empty() && !self.path.exists() {
+            return;
+        }
+        let sidecar = Sidecar {
+            annotations: self.items.clone(),
+        };
+        sidecar::save(&self.path, &sidecar);
+    }
+
+    /// Adds a point and starts typing its label.
+    pub fn add_point(&mut self, at: Direction) {
+        self.items.push(Annotation {
+            label: String::new(),
+            shape: Shape::Point { at },
+        });
+        self.editing = Some(Editing {
+            index: self.items.len() - 1,
+            original: None,
+        });
+    }
+
+    pub fn add_corner(&mut self, at: Direction) {
+        self.pending_region.push(at);
+    }
+
+    /// Turns the pending corners into a region and starts typing its label.
+    /// Regions need at least three corners; fewer are dropped.
+    pub fn finish_region(&mut self) {
+        let corners = std::mem::take(&mut self.pending_region);
+        if corners.len() < 3 {
+            return;
+        }
+        self.items.push(Annotation {
+            label: String::new(),
+            shape: Shape::Region { corners },
+        });
+        self.editing = Some(Editing {
+            index: self.items.len() - 1,
+            original: None,
+        });
+    }
+
+    /// Finds the annotation closest to `direction`, if any is within
+    /// `max_angle` radians.
+    pub fn nearest(&self, direction: Vec3, max_angle: f32) -> Option<usize> {
+        self.items
+            .iter()
+            .map(|item| item.angle_to(direction))
+            .enumerate()
+            .filter(|&(_, angle)| angle <= max_angle)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+
+    pub fn remove(&mut self, index: usize) {
+        self.items.remove(index);
+        self.save();
+    }
+
+    pub fn rename(&mut self, index: usize) {
+        self.editing = Some(Editing {
+            index,
+            original: Some(self.items[index].label.clone()),
+        });
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        if let Some(editing) = &self.editing {
+            self.items[editing.index].label.push_str(text);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some(editing) = &self.editing {
+            self.items[editing.index].label.pop();
+        }
+    }
+
+    /// Keeps the typed label and saves.
+    pub fn commit(&mut self) {
+        if self.editing.take().is_some() {
+            self.save();
+        }
+    }
+
+    /// Puts the label back, removing the annotation if it was new.
+    pub fn cancel(&mut self) {
+        if let Some(editing) = self.editing.take() {
+            match editing.original {
+                Some(label) => self.items[editing.index].label = label,
+                None => {
+                    self.items.remove(editing.index);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,86 @@
+//! The slow spin shown while nobody is using the viewer.
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use crate::config::AutoRotateConfig;
+use crate::pose::Pose;
+
+/// Seconds the spin takes to come up to speed.
+const RAMP_SECONDS: f32 = 2.0;
+
+/// Auto-rotate settings in radians and seconds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoRotate {
+    pub enabled: bool,
+    /// Yaw per second, negative to spin the other way.
+    pub speed: f32,
+    /// Seconds without input before the spin starts.
+    pub idle_timeout: f32,
+    /// How far the view drifts up and down from where it started.
+    pub pitch_drift: f32,
+    /// Seconds for one drift up and back down.
+    pub drift_period: f32,
+    /// Seconds to spin each image before moving to the next, or 0 to stay.
+    pub gallery_interval: f32,
+}
+
+impl AutoRotate {
+    pub fn new(config: &AutoRotateConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            speed: config.speed.to_radians(),
+            idle_timeout: config.idle_timeout.max(0.0),
+            pitch_drift: config.pitch_drift.to_radians(),
+            drift_period: config.drift_period,
+            gallery_interval: config.gallery_interval.max(0.0),
+        }
+    }
+
+    /// Gets the view `elapsed` seconds into spinning away from `from`. The
+    /// spin eases up to speed so it doesn't jerk into motion.
+    pub fn pose_at(&self, from: &Pose, elapsed: f32) -> Pose {
+        let spun = if elapsed < RAMP_SECONDS {
+            elapsed * elapsed / (2.0 * RAMP_SECONDS)
+        } else {
+            elapsed - RAMP_SECONDS / 2.0
+        };
+        self.spin(from, spun, elapsed)
+    }
+
+    /// Gets the view `elapsed` seconds into spinning at full speed from the
+    /// start, so that a whole turn loops without a pause.
+    pub fn steady_pose_at(&self, from: &Pose, elapsed: f32) -> Pose {
+        self.spin(from, elapsed, elapsed)
+    }
+
+    /// Turns `from` by `spun` seconds' worth of yaw and drifts its pitch to
+    /// where it is `elapsed` seconds in.
+    fn spin(&self, from: &Pose, spun: f32, elapsed: f32) -> Pose {
+        let [yaw, pitch, roll] = from.angles();
+        let drift = if self.drift_period > 0.0 {
+            self.pitch_drift * (TAU * elapsed / self.drift_period).sin()
+        } else {
+            0.0
+        };
+        Pose::from_angles(
+            [
+                yaw + self.speed * spun,
+                (pitch + drift).clamp(-FRAC_PI_2, FRAC_PI_2),
+                roll,
+            ],
+            from.fov,
+            from.zoom,
+        )
+    }
+
+    /// Gets the seconds a full turn takes at full speed, or `None` when
+    /// the spin is stopped.
+    pub fn turn_seconds(&self) -> Option<f32> {
+        (self.speed != 0.0).then(|| TAU / self.speed.abs())
+    }
+
+    /// Whether a showcase `elapsed` seconds in should move to the next image.
+    pub fn gallery_due(&self, elapsed: f32) -> bool {
+        self.gallery_interval > 0.0 && elapsed >= self.gallery_interval
+    }
+}
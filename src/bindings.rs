@@ -0,0 +1,355 @@
+use std::{collections::HashMap, ffi::CString};
+
+use fermium::{
+    keyboard::{SDL_GetKeyFromName, SDL_GetScancodeFromName},
+    keycode::SDL_Keycode,
+    scancode::SDL_Scancode,
+};
+use serde::Deserialize;
+
+/// Everything a key can be bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAction {
+    LookLeft,
+    LookRight,
+    LookUp,
+    LookDown,
+    RollLeft,
+    RollRight,
+    ZoomIn,
+    ZoomOut,
+    SourceScaleDown,
+    SourceScaleUp,
+    ToggleFreeLook,
+    ToggleLevelHorizon,
+    NextProjection,
+    ToggleOverview,
+    ToggleGrid,
+    ToggleHorizon,
+    ToggleCompass,
+    ToggleCompareLayout,
+    NextStereoOutput,
+    IpdDown,
+    IpdUp,
+    ConvergenceDown,
+    ConvergenceUp,
+    PanniniCompressionDown,
+    PanniniCompressionUp,
+    DeleteAnnotation,
+    RenameAnnotation,
+    AddKeyframe,
+    ClearPath,
+    PlayPath,
+    ToggleAutoRotate,
+    PreviousImage,
+    NextImage,
+    ToggleHud,
+    ToggleMinimap,
+    ToggleHelp,
+    Exit,
+}
+
+impl KeyAction {
+    pub const ALL: [KeyAction; 37] = [
+        KeyAction::LookLeft,
+        KeyAction::LookRight,
+        KeyAction::LookUp,
+        KeyAction::LookDown,
+        KeyAction::RollLeft,
+        KeyAction::RollRight,
+        KeyAction::ZoomIn,
+        KeyAction::ZoomOut,
+        KeyAction::SourceScaleDown,
+        KeyAction::SourceScaleUp,
+        KeyAction::ToggleFreeLook,
+        KeyAction::ToggleLevelHorizon,
+        KeyAction::NextProjection,
+        KeyAction::ToggleOverview,
+        KeyAction::ToggleGrid,
+        KeyAction::ToggleHorizon,
+        KeyAction::ToggleCompass,
+        KeyAction::ToggleCompareLayout,
+        KeyAction::NextStereoOutput,
+        KeyAction::IpdDown,
+        KeyAction::IpdUp,
+        KeyAction::ConvergenceDown,
+        KeyAction::ConvergenceUp,
+        KeyAction::PanniniCompressionDown,
+        KeyAction::PanniniCompressionUp,
+        KeyAction::DeleteAnnotation,
+        KeyAction::RenameAnnotation,
+        KeyAction::AddKeyframe,
+        KeyAction::ClearPath,
+        KeyAction::PlayPath,
+        KeyAction::ToggleAutoRotate,
+        KeyAction::PreviousImage,
+        KeyAction::NextImage,
+        KeyAction::ToggleHud,
+        KeyAction::ToggleMinimap,
+        KeyAction::ToggleHelp,
+        KeyAction::Exit,
+    ];
+
+    /// Gets a short description for the help screen.
+    pub fn description(self) -> &'static str {
+        match self {
+            KeyAction::LookLeft => "look left",
+            KeyAction::LookRight => "look right",
+            KeyAction::LookUp => "look up",
+            KeyAction::LookDown => "look down",
+            KeyAction::RollLeft => "roll left",
+            KeyAction::RollRight => "roll right",
+            KeyAction::ZoomIn => "zoom in",
+            KeyAction::ZoomOut => "zoom out",
+            KeyAction::SourceScaleDown => "shrink twin source",
+            KeyAction::SourceScaleUp => "grow twin source",
+            KeyAction::ToggleFreeLook => "free look",
+            KeyAction::ToggleLevelHorizon => "level horizon",
+            KeyAction::NextProjection => "next projection",
+            KeyAction::ToggleOverview => "overview map",
+            KeyAction::ToggleGrid => "latitude/longitude grid",
+            KeyAction::ToggleHorizon => "horizon line",
+            KeyAction::ToggleCompass => "compass",
+            KeyAction::ToggleCompareLayout => "side by side / wipe",
+            KeyAction::NextStereoOutput => "next stereo output",
+            KeyAction::IpdDown => "stereo views closer",
+            KeyAction::IpdUp => "stereo views apart",
+            KeyAction::ConvergenceDown => "eyes apart",
+            KeyAction::ConvergenceUp => "eyes together",
+            KeyAction::PanniniCompressionDown => "less Pannini compression",
+            KeyAction::PanniniCompressionUp => "more Pannini compression",
+            KeyAction::DeleteAnnotation => "delete annotation under cursor",
+            KeyAction::RenameAnnotation => "rename annotation under cursor",
+            KeyAction::AddKeyframe => "add view to camera path",
+            KeyAction::ClearPath => "clear camera path",
+            KeyAction::PlayPath => "play/stop camera path",
+            KeyAction::ToggleAutoRotate => "auto-rotate when idle",
+            KeyAction::PreviousImage => "previous image",
+            KeyAction::NextImage => "next image",
+            KeyAction::ToggleHud => "view information",
+            KeyAction::ToggleMinimap => "source image inset",
+            KeyAction::ToggleHelp => "this help",
+            KeyAction::Exit => "quit",
+        }
+    }
+}
+
+/// Whether key names refer to the symbol a key produces in the current
+/// layout or to its physical position on a US QWERTY keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyMode {
+    #[default]
+    Keycode,
+    Scancode,
+}
+
+/// Maps keys to actions.
+///
+/// Bindings live in the `[bindings]` section of the config and are written
+/// with SDL's key names, e.g.
+///
+/// ```toml
+/// [bindings]
+/// mode = "scancode"
+///
+/// [bindings.keys]
+/// look_left = ["Left", "A"]
+/// exit = ["Escape"]
+/// ```
+///
+/// Actions missing from the config keep their default keys.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub mode: KeyMode,
+    keys: HashMap<KeyAction, Vec<String>>,
+    #[serde(skip)]
+    resolved: HashMap<i32, KeyAction>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use KeyAction::*;
+        let defaults: [(KeyAction, &[&str]); 37] = [
+            (LookLeft, &["Left", "A"]),
+            (LookRight, &["Right", "D"]),
+            (LookUp, &["Up", "W"]),
+            (LookDown, &["Down", "S"]),
+            (RollLeft, &["Z"]),
+            (RollRight, &["C"]),
+            (ZoomIn, &["E"]),
+            (ZoomOut, &["Q"]),
+            (SourceScaleDown, &["[", "R"]),
+            (SourceScaleUp, &["]", "F"]),
+            (ToggleFreeLook, &["V"]),
+            (ToggleLevelHorizon, &["H"]),
+            (NextProjection, &["P"]),
+            (ToggleOverview, &["M"]),
+            (ToggleGrid, &["G"]),
+            (ToggleHorizon, &["O"]),
+            (ToggleCompass, &["N"]),
+            (ToggleCompareLayout, &["X"]),
+            (NextStereoOutput, &["B"]),
+            (IpdDown, &["Y"]),
+            (IpdUp, &["U"]),
+            (ConvergenceDown, &["J"]),
+            (ConvergenceUp, &["K"]),
+            (PanniniCompressionDown, &[","]),
+            (PanniniCompressionUp, &["."]),
+            (DeleteAnnotation, &["Delete"]),
+            (RenameAnnotation, &["F2"]),
+            (AddKeyframe, &["L"]),
+            (ClearPath, &["Backspace"]),
+            (PlayPath, &["Space"]),
+            (ToggleAutoRotate, &["T"]),
+            (PreviousImage, &["PageUp"]),
+            (NextImage, &["PageDown"]),
+            (ToggleHud, &["Tab"]),
+            (ToggleMinimap, &["I"]),
+            (ToggleHelp, &["F1"]),
+            (Exit, &["Escape"]),
+        ];
+        Self {
+            mode: KeyMode::Keycode,
+            keys: defaults
+                .iter()
+                .map(|(action, names)| (*action, names.iter().map(|n| n.to_string()).collect()))
+                .collect(),
+            resolved: HashMap::new(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Gives every action the file left out its default keys. A key the file
+    /// binds is never also given a default action.
+    pub fn fill_defaults(&mut self) {
+        let taken: Vec<String> = self.keys.values().flatten().cloned().collect();
+        for (action, names) in Self::default().keys {
+            self.keys.entry(action).or_insert_with(|| {
+                names
+                    .into_iter()
+                    .filter(|name| !taken.iter().any(|t| t.eq_ignore_ascii_case(name)))
+                    .collect()
+            });
+        }
+    }
+
+    /// Looks up every key name with SDL. Must be called after SDL is
+    /// initialised and before [`action`](Self::action).
+    ///
+    /// Unknown key names are reported and skipped. A key bound to two
+    /// different actions is an error naming both.
+    pub fn resolve(&mut self) -> Result<(), String> {
+        let mode = self.mode;
+        self.resolve_with(|name| {
+            let c_name = CString::new(name).unwrap_or_default();
+            unsafe {
+                match mode {
+                    KeyMode::Keycode => SDL_GetKeyFromName(c_name.as_ptr()).0,
+                    KeyMode::Scancode => SDL_GetScancodeFromName(c_name.as_ptr()).0,
+                }
+            }
+        })
+    }
+
+    /// Resolves key names with `lookup`, which gives 0 for unknown names.
+    fn resolve_with(&mut self, lookup: impl Fn(&str) -> i32) -> Result<(), String> {
+        let mut resolved = HashMap::new();
+        for action in KeyAction::ALL {
+            for name in self.keys(action) {
+                let code = lookup(name);
+                if code == 0 {
+                    println!("Unknown key name {:?} bound to {:?}", name, action);
+                    continue;
+                }
+                match resolved.insert(code, action) {
+                    Some(other) if other != action => {
+                        return Err(format!(
+                            "Key {:?} is bound to both {:?} and {:?}",
+                            name, other, action
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+        self.resolved = resolved;
+        Ok(())
+    }
+
+    /// Gets the names of the keys bound to an action.
+    pub fn keys(&self, action: KeyAction) -> &[String] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Gets the action bound to a key event, if any.
+    pub fn action(&self, keycode: SDL_Keycode, scancode: SDL_Scancode) -> Option<KeyAction> {
+        let code = match self.mode {
+            KeyMode::Keycode => keycode.0,
+            KeyMode::Scancode => scancode.0,
+        };
+        self.resolved.get(&code).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stands in for SDL's key names: every default name is known, matched
+    /// without regard to case, and anything else is unknown.
+    fn lookup(name: &str) -> i32 {
+        let defaults = KeyBindings::default();
+        let mut names: Vec<&String> = defaults.keys.values().flatten().collect();
+        names.sort();
+        names
+            .iter()
+            .position(|known| known.eq_ignore_ascii_case(name))
+            .map_or(0, |i| i as i32 + 1)
+    }
+
+    fn bindings(toml: &str) -> KeyBindings {
+        let mut bindings: KeyBindings = toml::from_str(toml).unwrap();
+        bindings.fill_defaults();
+        bindings
+    }
+
+    #[test]
+    fn rebinding_frees_the_default_key() {
+        // Q zooms out by default.
+        let mut bindings = bindings("[keys]\nexit = [\"q\"]");
+        assert!(bindings.keys(KeyAction::ZoomOut).is_empty());
+        assert_eq!(bindings.keys(KeyAction::ZoomIn), ["E"]);
+        assert_eq!(bindings.resolve_with(lookup), Ok(()));
+        assert_eq!(bindings.resolved.get(&lookup("Q")), Some(&KeyAction::Exit));
+        assert_eq!(bindings.resolved.get(&lookup("Escape")), None);
+    }
+
+    #[test]
+    fn binding_a_key_to_two_actions_fails() {
+        let mut bindings = bindings("[keys]\nexit = [\"Q\"]\nzoom_in = [\"q\"]");
+        let error = bindings.resolve_with(lookup).unwrap_err();
+        assert!(
+            error.contains("Exit") && error.contains("ZoomIn"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn unknown_key_names_are_skipped() {
+        let mut bindings = bindings("[keys]\nexit = [\"NoSuchKey\", \"Escape\"]");
+        assert_eq!(bindings.resolve_with(lookup), Ok(()));
+        assert_eq!(
+            bindings.resolved.get(&lookup("Escape")),
+            Some(&KeyAction::Exit)
+        );
+        assert_eq!(bindings.resolved.get(&0), None);
+        let mut defaults = KeyBindings::default();
+        defaults.resolve_with(lookup).unwrap();
+        assert_eq!(bindings.resolved, defaults.resolved);
+    }
+}
@@ -0,0 +1,83 @@
+//! Saved views of an image, recalled with the number keys and kept in a JSON
+//! sidecar next to it, e.g. `bridge.jpg.bookmarks.json`:
+//!
+//! ```json
+//! {
+//!   "bookmarks": {
+//!     "1": { "yaw": 12.5, "pitch": -3.0, "roll": 0.0, "fov": 60.0, "zoom": 1.0 }
+//!   }
+//! }
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::pose::Pose;
+use crate::sidecar;
+
+/// A saved pose, with angles in degrees as the HUD shows them.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct View {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+    pub fov: f32,
+    pub zoom: f32,
+}
+
+impl View {
+    pub fn from_pose(pose: &Pose) -> Self {
+        let [yaw, pitch, roll] = pose.angles().map(f32::to_degrees);
+        Self {
+            yaw,
+            pitch,
+            roll,
+            fov: pose.fov.to_degrees(),
+            zoom: pose.zoom,
+        }
+    }
+
+    pub fn to_pose(self) -> Pose {
+        let angles = [self.yaw, self.pitch, self.roll].map(f32::to_radians);
+        Pose::from_angles(angles, self.fov.to_radians(), self.zoom)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Sidecar {
+    bookmarks: BTreeMap<u8, View>,
+}
+
+/// The bookmarks of the image on screen, by number key.
+pub struct Bookmarks {
+    slots: BTreeMap<u8, View>,
+    path: PathBuf,
+}
+
+impl Bookmarks {
+    /// Reads an image's sidecar.
+    pub fn load(image: &Path) -> Self {
+        let path = sidecar::path(image, "bookmarks");
+        let sidecar: Sidecar = sidecar::load(&path);
+        Self {
+            slots: sidecar.bookmarks,
+            path,
+        }
+    }
+
+    pub fn get(&self, slot: u8) -> Option<Pose> {
+        self.slots.get(&slot).map(|view| view.to_pose())
+    }
+
+    /// Saves a pose under a number key and writes the sidecar.
+    pub fn set(&mut self, slot: u8, pose: &Pose) {
+        self.slots.insert(slot, View::from_pose(pose));
+        let sidecar = Sidecar {
+            bookmarks: self.slots.clone(),
+        };
+        sidecar::save(&self.path, &sidecar);
+    }
+}
@@ -0,0 +1,264 @@
+//! Keyframed camera moves, recorded from the live view and kept in a JSON
+//! sidecar next to the image, e.g. `bridge.jpg.path.json`:
+//!
+//! ```json
+//! {
+//!   "keyframes": [
+//!     { "time": 0.0, "yaw": 0.0, "pitch": 0.0, "roll": 0.0, "fov": 90.0, "zoom": 1.0 },
+//!     { "time": 4.0, "yaw": 120.0, "pitch": -10.0, "roll": 0.0, "fov": 60.0, "zoom": 1.0 }
+//!   ]
+//! }
+//! ```
+//!
+//! Times are in seconds and angles in degrees. Yaw and roll are taken as
+//! written rather than wrapped, so going from 0 to 720 spins twice.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::bookmarks::View;
+use crate::pose::Pose;
+use crate::sidecar;
+
+/// Seconds between a keyframe and the next when there's no live timing to
+/// go by, e.g. the first one added after loading a path.
+const DEFAULT_GAP: f32 = 2.0;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Keyframe {
+    pub time: f32,
+    #[serde(flatten)]
+    pub view: View,
+}
+
+impl Keyframe {
+    fn values(&self) -> [f32; 5] {
+        let view = &self.view;
+        [view.yaw, view.pitch, view.roll, view.fov, view.zoom]
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CameraPath {
+    /// Sorted by time.
+    pub keyframes: Vec<Keyframe>,
+    #[serde(skip)]
+    file: PathBuf,
+    /// When the last keyframe was added this session.
+    #[serde(skip)]
+    last_added: Option<Instant>,
+}
+
+impl CameraPath {
+    /// Reads an image's sidecar.
+    pub fn load(image: &Path) -> Self {
+        let file = sidecar::path(image, "path");
+        let mut path: CameraPath = sidecar::load(&file);
+        path.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        path.file = file;
+        path
+    }
+
+    fn save(&self) {
+        sidecar::save(&self.file, self);
+    }
+
+    /// Appends a keyframe as far after the last as the time since it was
+    /// added, and saves.
+    pub fn add(&mut self, pose: &Pose) {
+        let mut view = View::from_pose(pose);
+        let time = match self.keyframes.last() {
+            None => 0.0,
+            Some(last) => {
+                // Turn the short way from the last keyframe.
+                view.yaw = unwrap_degrees(view.yaw, last.view.yaw);
+                view.roll = unwrap_degrees(view.roll, last.view.roll);
+                let gap = self
+                    .last_added
+                    .map_or(DEFAULT_GAP, |added| added.elapsed().as_secs_f32());
+                last.time + gap
+            }
+        };
+        self.keyframes.push(Keyframe { time, view });
+        self.last_added = Some(Instant::now());
+        self.save();
+    }
+
+    /// Removes every keyframe and saves.
+    pub fn clear(&mut self) {
+        self.keyframes.clear();
+        self.last_added = None;
+        self.save();
+    }
+
+    /// Gets the time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |last| last.time)
+    }
+
+    /// Gets the pose `time` seconds in, on a Catmull-Rom spline through the
+    /// keyframes. Holds the first and last keyframes outside their times.
+    pub fn pose_at(&self, time: f32) -> Option<Pose> {
+        let keys = &self.keyframes;
+        let first = keys.first()?;
+        let last = keys.last()?;
+        if time <= first.time {
+            return Some(first.view.to_pose());
+        }
+        if time >= last.time {
+            return Some(last.view.to_pose());
+        }
+        let i = keys.partition_point(|key| key.time <= time) - 1;
+        let (a, b) = (&keys[i], &keys[i + 1]);
+        let span = b.time - a.time;
+        if span <= 0.0 {
+            return Some(b.view.to_pose());
+        }
+        let s = (time - a.time) / span;
+        let (ma, mb) = (self.tangent(i), self.tangent(i + 1));
+        // Cubic Hermite basis.
+        let h00 = 2.0 * s * s * s - 3.0 * s * s + 1.0;
+        let h10 = s * s * s - 2.0 * s * s + s;
+        let h01 = -2.0 * s * s * s + 3.0 * s * s;
+        let h11 = s * s * s - s * s;
+        let (va, vb) = (a.values(), b.values());
+        let [yaw, pitch, roll, fov, zoom] = std::array::from_fn(|c| {
+            h00 * va[c] + h10 * span * ma[c] + h01 * vb[c] + h11 * span * mb[c]
+        });
+        let view = View {
+            yaw,
+            pitch,
+            roll,
+            fov,
+            zoom,
+        };
+        Some(view.to_pose())
+    }
+
+    /// Gets the rate of change of every value at keyframe `i`, from its
+    /// neighbours or, at the ends, the one neighbour it has.
+    fn tangent(&self, i: usize) -> [f32; 5] {
+        let keys = &self.keyframes;
+        let before = &keys[i.saturating_sub(1)];
+        let after = &keys[(i + 1).min(keys.len() - 1)];
+        let span = after.time - before.time;
+        let (vb, va) = (before.values(), after.values());
+        std::array::from_fn(|c| {
+            if span > 0.0 {
+                (va[c] - vb[c]) / span
+            } else {
+                0.0
+            }
+        })
+    }
+}
+
+/// Shifts `degrees` by whole turns to land within half a turn of `near`.
+fn unwrap_degrees(degrees: f32, near: f32) -> f32 {
+    degrees + 360.0 * ((near - degrees) / 360.0).round()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(time: f32, yaw: f32, pitch: f32, fov: f32) -> Keyframe {
+        Keyframe {
+            time,
+            view: View {
+                yaw,
+                pitch,
+                roll: 0.0,
+                fov,
+                zoom: 1.0,
+            },
+        }
+    }
+
+    fn path(keyframes: Vec<Keyframe>) -> CameraPath {
+        CameraPath {
+            keyframes,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn passes_through_every_keyframe() {
+        let keys = vec![
+            key(0.0, 0.0, 0.0, 90.0),
+            key(2.0, 45.0, 10.0, 70.0),
+            key(3.5, 120.0, -20.0, 60.0),
+            key(6.0, 200.0, 5.0, 100.0),
+        ];
+        let path = path(keys.clone());
+        for key in keys {
+            assert_eq!(path.pose_at(key.time), Some(key.view.to_pose()));
+        }
+    }
+
+    #[test]
+    fn holds_the_end_keyframes_outside_the_path() {
+        let first = key(1.0, 10.0, 0.0, 90.0);
+        let last = key(4.0, 80.0, 30.0, 50.0);
+        let path = path(vec![first, key(2.0, 40.0, 20.0, 70.0), last]);
+        assert_eq!(path.pose_at(-5.0), Some(first.view.to_pose()));
+        assert_eq!(path.pose_at(0.5), Some(first.view.to_pose()));
+        assert_eq!(path.pose_at(4.5), Some(last.view.to_pose()));
+        assert_eq!(path.pose_at(100.0), Some(last.view.to_pose()));
+    }
+
+    #[test]
+    fn turns_the_short_way_past_north() {
+        let mut path = CameraPath {
+            file: std::env::temp_dir().join(format!("path-{}.json", std::process::id())),
+            ..Default::default()
+        };
+        path.add(&key(0.0, 350.0, 0.0, 90.0).view.to_pose());
+        path.add(&key(0.0, 10.0, 0.0, 90.0).view.to_pose());
+        std::fs::remove_file(&path.file).unwrap();
+        let [from, to] = [0, 1].map(|i| path.keyframes[i].view.yaw);
+        assert!((to - from - 20.0).abs() < 1e-3, "{} to {}", from, to);
+        // Halfway is due north, not due south.
+        let halfway = path.pose_at(path.duration() / 2.0).unwrap();
+        let yaw = View::from_pose(&halfway).yaw;
+        assert!(yaw.abs() < 1e-2, "{}", yaw);
+    }
+
+    #[test]
+    fn holds_a_single_keyframe() {
+        let only = key(3.0, 30.0, 10.0, 80.0);
+        let path = path(vec![only]);
+        for time in [0.0, 3.0, 10.0] {
+            assert_eq!(path.pose_at(time), Some(only.view.to_pose()));
+        }
+        assert_eq!(CameraPath::default().pose_at(0.0), None);
+    }
+
+    #[test]
+    fn jumps_between_keyframes_at_the_same_time() {
+        let before = key(2.0, 40.0, 0.0, 90.0);
+        let after = key(2.0, 90.0, 0.0, 60.0);
+        let path = path(vec![
+            key(0.0, 0.0, 0.0, 90.0),
+            before,
+            after,
+            key(4.0, 120.0, 0.0, 60.0),
+        ]);
+        assert_eq!(path.pose_at(2.0), Some(after.view.to_pose()));
+        for step in 0..=40 {
+            let pose = path.pose_at(step as f32 / 10.0).unwrap();
+            let view = View::from_pose(&pose);
+            assert!(
+                [view.yaw, view.pitch, view.roll, view.fov, view.zoom]
+                    .iter()
+                    .all(|value| value.is_finite()),
+                "{:?} at {}",
+                view,
+                step
+            );
+        }
+    }
+}
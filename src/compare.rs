@@ -0,0 +1,103 @@
+use crate::Source;
+
+/// How two panoramas share the window when comparing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareLayout {
+    /// Each image gets its own half of the window.
+    SideBySide,
+    /// Both images fill the window, split by a draggable vertical divider.
+    Wipe,
+}
+
+/// One image's share of the window.
+pub struct Pane {
+    /// Whether this pane shows the second image.
+    pub second: bool,
+    /// Where the view is laid out, as `[x, y, width, height]`.
+    pub region: [i32; 4],
+    /// Which part of the region is drawn, as `[x, y, width, height]`.
+    pub clip: [i32; 4],
+}
+
+/// Settings of a side by side or wipe comparison. Both images are always
+/// seen through the same camera.
+#[derive(Clone)]
+pub struct Comparison {
+    /// How the second image maps directions, which may differ from the first
+    /// even when both show the same file.
+    pub source: Source,
+    pub layout: CompareLayout,
+    /// Wipe divider position as a fraction of the window width.
+    pub divider: f32,
+}
+
+impl Comparison {
+    pub fn new(source: Source) -> Self {
+        Self {
+            source,
+            layout: CompareLayout::SideBySide,
+            divider: 0.5,
+        }
+    }
+
+    pub fn toggle_layout(&mut self) {
+        self.layout = match self.layout {
+            CompareLayout::SideBySide => CompareLayout::Wipe,
+            CompareLayout::Wipe => CompareLayout::SideBySide,
+        };
+    }
+
+    /// Splits a `width` by `height` window between the two images.
+    pub fn panes(&self, width: i32, height: i32) -> [Pane; 2] {
+        match self.layout {
+            CompareLayout::SideBySide => {
+                let half = width / 2;
+                let first = [0, 0, half, height];
+                let second = [half, 0, width - half, height];
+                [
+                    Pane {
+                        second: false,
+                        region: first,
+                        clip: first,
+                    },
+                    Pane {
+                        second: true,
+                        region: second,
+                        clip: second,
+                    },
+                ]
+            }
+            CompareLayout::Wipe => {
+                let full = [0, 0, width, height];
+                let split = self.divider_x(width);
+                [
+                    Pane {
+                        second: false,
+                        region: full,
+                        clip: [0, 0, split, height],
+                    },
+                    Pane {
+                        second: true,
+                        region: full,
+                        clip: [split, 0, width - split, height],
+                    },
+                ]
+            }
+        }
+    }
+
+    /// Gets the wipe divider's pixel column in a `width` wide window.
+    pub fn divider_x(&self, width: i32) -> i32 {
+        (self.divider * width as f32).round() as i32
+    }
+
+    /// Whether `x`, in a `width` wide window, is close enough to the wipe
+    /// divider to grab it.
+    pub fn near_divider(&self, x: i32, width: i32) -> bool {
+        self.layout == CompareLayout::Wipe && (x - self.divider_x(width)).abs() <= 6
+    }
+
+    pub fn set_divider(&mut self, x: i32, width: i32) {
+        self.divider = (x as f32 / width.max(1) as f32).clamp(0.0, 1.0);
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::bindings::KeyBindings;
+use crate::slideshow::Transition;
+use crate::stereo::StereoLayout;
+
+/// Name of the per-directory override file, looked up in the working directory.
+const LOCAL_CONFIG: &str = "360-photo-viewer.toml";
+
+/// Name of the standalone key bindings file from before bindings moved into
+/// the config, still looked up in the working directory.
+const LOCAL_BINDINGS: &str = "bindings.toml";
+
+/// User tunable settings.
+///
+/// Read from `config.toml` in the user's config directory, then from
+/// `360-photo-viewer.toml` in the working directory and finally from any file
+/// passed with `--config`. Each file only needs the settings it changes, and
+/// later files win.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub window: WindowConfig,
+    pub camera: CameraConfig,
+    pub gamepad: GamepadConfig,
+    pub stereo: StereoConfig,
+    pub hud: HudConfig,
+    pub overlays: OverlayConfig,
+    pub auto_rotate: AutoRotateConfig,
+    pub slideshow: SlideshowConfig,
+    pub bindings: KeyBindings,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub width: i32,
+    pub height: i32,
+    /// RGBA colour drawn behind the image.
+    pub clear_color: [f32; 4],
+    /// Checks GL calls for errors and prints them.
+    pub gl_debug: bool,
+}
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            width: 800,
+            height: 800,
+            clear_color: [0.0, 0.0, 0.0, 1.0],
+            gl_debug: false,
+        }
+    }
+}
+
+/// Camera motion settings. Angles are in degrees.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    /// Starting field of view.
+    pub fov: f32,
+    /// Top look speed per second.
+    pub rotation_speed: f32,
+    /// Top field of view change per second.
+    pub fov_speed: f32,
+    /// Top twin view zoom change per second.
+    pub zoom_speed: f32,
+    /// How many times its top speed a motion gains per second while held.
+    pub acceleration: f32,
+    /// Exponential decay rate of motion once input stops.
+    pub damping: f32,
+}
+impl Default for CameraConfig {
+    fn default() -> Self {
+        Self {
+            fov: 90.0,
+            rotation_speed: 103.0,
+            fov_speed: 172.0,
+            zoom_speed: 0.018,
+            acceleration: 6.0,
+            damping: 8.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GamepadConfig {
+    /// Fraction of stick travel around the centre that is ignored.
+    pub stick_dead_zone: f32,
+    /// Fraction of trigger travel that is ignored.
+    pub trigger_dead_zone: f32,
+}
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            stick_dead_zone: 0.2,
+            trigger_dead_zone: 0.05,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StereoConfig {
+    /// How stereo images pack their eyes; overridden by `--stereo`.
+    pub layout: StereoLayout,
+    /// Distance between the centres of the side by side views, as a fraction
+    /// of the window width.
+    pub ipd: f32,
+    /// Degrees each eye turns in towards the other.
+    pub convergence: f32,
+}
+impl Default for StereoConfig {
+    fn default() -> Self {
+        Self {
+            layout: StereoLayout::Mono,
+            ipd: 0.5,
+            convergence: 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HudConfig {
+    /// Whether view information is shown at startup.
+    pub visible: bool,
+    /// Whether the source image inset is shown at startup.
+    pub minimap: bool,
+    /// Screen pixels per font pixel.
+    pub scale: i32,
+}
+impl Default for HudConfig {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            minimap: false,
+            scale: 2,
+        }
+    }
+}
+
+/// Which orientation aids are shown at startup.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OverlayConfig {
+    pub grid: bool,
+    pub horizon: bool,
+    pub compass: bool,
+}
+
+/// Spinning the view while nobody is using the viewer, e.g. on a kiosk.
+/// Angles are in degrees.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AutoRotateConfig {
+    /// Whether the view spins after `idle_timeout`.
+    pub enabled: bool,
+    /// Yaw per second, negative to spin the other way.
+    pub speed: f32,
+    /// Seconds without input before spinning starts.
+    pub idle_timeout: f32,
+    /// How far the view drifts up and down from its starting pitch.
+    pub pitch_drift: f32,
+    /// Seconds for one drift up and back down.
+    pub drift_period: f32,
+    /// Seconds spent on each image before moving to the next, or 0 to stay.
+    pub gallery_interval: f32,
+}
+impl Default for AutoRotateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            speed: 6.0,
+            idle_timeout: 30.0,
+            pitch_drift: 5.0,
+            drift_period: 40.0,
+            gallery_interval: 0.0,
+        }
+    }
+}
+
+/// Stepping through the images by themselves, e.g. for a presentation.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SlideshowConfig {
+    /// Whether the slideshow starts with the viewer; also set by `--slideshow`.
+    pub enabled: bool,
+    /// Seconds each image is shown for.
+    pub dwell: f32,
+    /// How each image gives way to the next.
+    pub transition: Transition,
+    /// Seconds the transition takes.
+    pub transition_seconds: f32,
+}
+impl Default for SlideshowConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dwell: 10.0,
+            transition: Transition::Crossfade,
+            transition_seconds: 1.5,
+        }
+    }
+}
+
+impl Config {
+    /// Gets the user wide config file, e.g. `~/.config/360-photo-viewer/config.toml`.
+    pub fn user_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("360-photo-viewer").join("config.toml"))
+    }
+
+    /// Layers the user config, the working directory override and `extra` (in
+    /// that order) over the defaults. Missing optional files are skipped, but
+    /// a missing `extra` file is an error.
+    ///
+    /// A standalone bindings file, `bindings` or else `bindings.toml` in the
+    /// working directory, is then layered over the `[bindings]` section.
+    pub fn load(extra: Option<&Path>, bindings: Option<&Path>) -> Result<Self, String> {
+        let mut merged = toml::Table::new();
+        let optional = Self::user_path()
+            .into_iter()
+            .chain(Some(PathBuf::from(LOCAL_CONFIG)));
+        for path in optional {
+            if path.exists() {
+                merge(&mut merged, read_table(&path)?);
+            }
+        }
+        if let Some(path) = extra {
+            merge(&mut merged, read_table(path)?);
+        }
+        let local_bindings = Path::new(LOCAL_BINDINGS);
+        let bindings = bindings.or_else(|| local_bindings.exists().then_some(local_bindings));
+        if let Some(path) = bindings {
+            let mut table = toml::Table::new();
+            table.insert("bindings".to_string(), read_table(path)?.into());
+            merge(&mut merged, table);
+        }
+        let mut config: Self = merged
+            .try_into()
+            .map_err(|e: toml::de::Error| e.to_string())?;
+        config.bindings.fill_defaults();
+        Ok(config)
+    }
+}
+
+fn read_table(path: &Path) -> Result<toml::Table, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    text.parse::<toml::Table>()
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Recursively copies `overlay` into `base`, replacing everything but tables.
+fn merge(base: &mut toml::Table, overlay: toml::Table) {
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base_table)), toml::Value::Table(overlay_table)) => {
+                merge(base_table, overlay_table)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layered(files: &[&str]) -> Result<Config, String> {
+        let mut merged = toml::Table::new();
+        for text in files {
+            merge(&mut merged, text.parse().unwrap());
+        }
+        merged
+            .try_into()
+            .map_err(|e: toml::de::Error| e.to_string())
+    }
+
+    #[test]
+    fn later_files_override_only_what_they_set() {
+        let shared =
+            "[window]\nwidth = 1920\nheight = 1080\n\n[camera]\nfov = 100.0\ndamping = 4.0\n";
+        let personal = "[window]\nheight = 1200\n\n[camera]\ndamping = 2.0\n";
+        let config = layered(&[shared, personal]).unwrap();
+        // Overridden by the later file.
+        assert_eq!(config.window.height, 1200);
+        assert_eq!(config.camera.damping, 2.0);
+        // Inherited from the earlier file.
+        assert_eq!(config.window.width, 1920);
+        assert_eq!(config.camera.fov, 100.0);
+        // Set by neither.
+        let defaults = Config::default();
+        assert_eq!(config.camera.rotation_speed, defaults.camera.rotation_speed);
+        assert_eq!(config.window.clear_color, defaults.window.clear_color);
+    }
+
+    #[test]
+    fn misspelt_keys_are_rejected() {
+        let error = layered(&["[camera]\nfov = 80.0\n", "[camera]\ndampning = 3.0\n"]).unwrap_err();
+        assert!(error.contains("dampning"), "{}", error);
+        assert!(layered(&["[windw]\nwidth = 640\n"]).is_err());
+    }
+}
@@ -0,0 +1,782 @@
+use beryllium::{
+    controller::{ControllerAxis, ControllerButton, GameController},
+    events::Event,
+    Sdl,
+};
+use fermium::{
+    keyboard::{SDL_GetModState, SDL_StartTextInput, SDL_StopTextInput},
+    keycode::{
+        SDL_Keycode, KMOD_CTRL, KMOD_SHIFT, SDLK_0, SDLK_1, SDLK_9, SDLK_BACKSPACE, SDLK_ESCAPE,
+        SDLK_KP_ENTER, SDLK_RETURN,
+    },
+};
+use ultraviolet::Vec3;
+
+use crate::annotations::{Annotations, Direction};
+use crate::auto_rotate::AutoRotate;
+use crate::bindings::{KeyAction, KeyBindings};
+use crate::camera_path::CameraPath;
+use crate::config::Config;
+use crate::pose::{Glide, Pose};
+use crate::tour::Scene;
+use crate::RotImage;
+
+/// SDL's index for the left mouse button.
+const MOUSE_BUTTON_LEFT: u8 = 1;
+/// SDL's index for the right mouse button.
+const MOUSE_BUTTON_RIGHT: u8 = 3;
+
+/// How far in pixels from an annotation or hotspot the cursor can be to pick
+/// it.
+const PICK_RADIUS: f32 = 12.0;
+
+/// A drag released later than this after the last motion does not fling.
+const FLICK_WINDOW_MS: u32 = 60;
+
+/// Velocities below this are treated as stopped.
+const REST_EPSILON: f32 = 1e-4;
+
+/// Seconds taken to glide to a bookmark.
+const GLIDE_SECONDS: f32 = 0.8;
+
+/// Something outside of the camera that the viewer has to act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Exit,
+    NextImage,
+    PreviousImage,
+    /// Save the current view under a number key.
+    SaveBookmark(u8),
+    /// Glide to the view saved under a number key.
+    RecallBookmark(u8),
+    /// Add the current view to the camera path.
+    AddKeyframe,
+    ClearPath,
+    /// Start or stop playing the camera path.
+    PlayPath,
+    /// Go to the scene a hotspot of the current one leads to.
+    FollowHotspot(usize),
+    /// The window changed size.
+    Resized,
+    ToggleHud,
+    ToggleMinimap,
+    ToggleHelp,
+}
+
+/// Camera motion that plays by itself until the camera is steered.
+enum Animation {
+    /// A move to a recalled bookmark.
+    Glide(Glide),
+    /// Playback of a camera path, `elapsed` seconds in.
+    Path { path: CameraPath, elapsed: f32 },
+    /// The idle spin, `elapsed` seconds after it set off from `from`.
+    Showcase { from: Pose, elapsed: f32 },
+}
+
+pub struct CameraController {
+    /// Held look keys as `[yaw, pitch, roll]` in the range `-1.0..=1.0`.
+    rot_input: [f32; 3],
+    fov_input: f32,
+    zoom_input: f32,
+    /// Current angular velocity as `[yaw, pitch, roll]` in radians per second.
+    rot_velocity: [f32; 3],
+    fov_velocity: f32,
+    zoom_velocity: f32,
+    dragging: bool,
+    /// Whether the left button is holding the comparison wipe divider.
+    dragging_divider: bool,
+    /// Smoothed angular velocity of the current drag, used for flicks.
+    drag_velocity: [f32; 3],
+    last_drag_time: u32,
+    animation: Option<Animation>,
+    /// Seconds since the last input.
+    idle: f32,
+    /// Set when an event rotated the camera since the last `update`.
+    rotated: bool,
+    /// Window size in screen coordinates, as mouse events report them.
+    window_size: [i32; 2],
+    /// Last known cursor position in screen coordinates.
+    mouse: [i32; 2],
+    /// Whether SDL is sending text for an annotation label.
+    text_input: bool,
+    gamepads: Vec<GameController>,
+    /// Stick positions as `[left x, left y, right x, right y]` in `-1.0..=1.0`.
+    sticks: [f32; 4],
+    /// Trigger positions as `[left, right]` in `0.0..=1.0`.
+    triggers: [f32; 2],
+    pub bindings: KeyBindings,
+    pub auto_rotate: AutoRotate,
+    /// Fraction of stick travel around the centre that is ignored.
+    pub stick_dead_zone: f32,
+    /// Fraction of trigger travel that is ignored.
+    pub trigger_dead_zone: f32,
+    /// Top look speed in radians per second.
+    pub camera_rot_speed: f32,
+    /// Top field of view change in radians per second.
+    pub fov_scale_speed: f32,
+    /// Top twin view zoom change per second.
+    pub zoom_scale_speed: f32,
+    /// How many times its top speed a motion gains per second while held.
+    pub acceleration: f32,
+    /// Exponential decay rate of motion once input stops.
+    pub damping: f32,
+}
+impl CameraController {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            rot_input: [0.0, 0.0, 0.0],
+            fov_input: 0.0,
+            zoom_input: 0.0,
+            rot_velocity: [0.0, 0.0, 0.0],
+            fov_velocity: 0.0,
+            zoom_velocity: 0.0,
+            dragging: false,
+            dragging_divider: false,
+            drag_velocity: [0.0, 0.0, 0.0],
+            last_drag_time: 0,
+            animation: None,
+            idle: 0.0,
+            rotated: false,
+            window_size: [config.window.width.max(1), config.window.height.max(1)],
+            mouse: [0, 0],
+            text_input: false,
+            gamepads: Vec::new(),
+            sticks: [0.0; 4],
+            triggers: [0.0; 2],
+            bindings: config.bindings.clone(),
+            auto_rotate: AutoRotate::new(&config.auto_rotate),
+            stick_dead_zone: config.gamepad.stick_dead_zone,
+            trigger_dead_zone: config.gamepad.trigger_dead_zone,
+            camera_rot_speed: config.camera.rotation_speed.to_radians(),
+            fov_scale_speed: config.camera.fov_speed.to_radians(),
+            zoom_scale_speed: config.camera.zoom_speed,
+            acceleration: config.camera.acceleration,
+            damping: config.camera.damping,
+        }
+    }
+
+    /// Drains the SDL event queue, returning the actions the viewer should take.
+    ///
+    /// Mouse drags rotate the camera immediately; everything else only updates
+    /// the held input state, which is integrated by [`update`](Self::update).
+    /// While an annotation label is being typed, keys edit the label instead
+    /// of running their bindings.
+    pub fn handle_inputs(
+        &mut self,
+        sdl: &Sdl,
+        image: &mut RotImage,
+        annotations: &mut Annotations,
+        scene: &Scene,
+    ) -> Vec<Action> {
+        let mut actions = Vec::new();
+        while let Some((event, timestamp)) = sdl.poll_events() {
+            if let Event::MouseMotion { x_win, y_win, .. } = event {
+                self.mouse = [x_win, y_win];
+            }
+            if matches!(
+                event,
+                Event::Key { .. }
+                    | Event::TextInput { .. }
+                    | Event::MouseButton { .. }
+                    | Event::MouseMotion { .. }
+                    | Event::MouseWheel { .. }
+                    | Event::ControllerButton { .. }
+            ) {
+                self.wake();
+            }
+            match event {
+                Event::Quit => {
+                    actions.push(Action::Exit);
+                    break;
+                }
+                Event::ControllerAdded { index } => match sdl.open_game_controller(index) {
+                    Ok(gamepad) => {
+                        println!("Connected controller: {}", gamepad.get_name());
+                        self.gamepads.push(gamepad);
+                    }
+                    Err(e) => println!("Couldn't open controller {}: {:?}", index, e),
+                },
+                Event::ControllerRemoved { .. } => {
+                    self.gamepads.retain(|gamepad| gamepad.is_attached());
+                    if self.gamepads.is_empty() {
+                        self.sticks = [0.0; 4];
+                        self.triggers = [0.0; 2];
+                    }
+                }
+                Event::ControllerAxis { axis, value, .. } => {
+                    let stick = value as f32 / i16::MAX as f32;
+                    match axis {
+                        ControllerAxis::LeftX => self.sticks[0] = stick,
+                        ControllerAxis::LeftY => self.sticks[1] = stick,
+                        ControllerAxis::RightX => self.sticks[2] = stick,
+                        ControllerAxis::RightY => self.sticks[3] = stick,
+                        ControllerAxis::TriggerLeft => self.triggers[0] = stick.max(0.0),
+                        ControllerAxis::TriggerRight => self.triggers[1] = stick.max(0.0),
+                        ControllerAxis::Invalid => {}
+                    }
+                }
+                Event::ControllerButton {
+                    button,
+                    pressed: true,
+                    ..
+                } => match button {
+                    ControllerButton::LeftShoulder => actions.push(Action::PreviousImage),
+                    ControllerButton::RightShoulder => actions.push(Action::NextImage),
+                    _ => {}
+                },
+                Event::WindowSizeChanged { width, height, .. } => {
+                    self.window_size = [width.max(1), height.max(1)];
+                    actions.push(Action::Resized);
+                }
+                Event::MouseButton {
+                    button: MOUSE_BUTTON_LEFT,
+                    pressed,
+                    x,
+                    y,
+                    ..
+                } => {
+                    let hotspot = if pressed {
+                        self.pick(image, [x, y])
+                            .and_then(|(ray, radius)| scene.hotspot_at(ray, radius))
+                    } else {
+                        None
+                    };
+                    if pressed && image.overview {
+                        let [width, height] = self.window_size;
+                        let aspect = width as f32 / height as f32;
+                        if let Some((lon, lat)) =
+                            RotImage::overview_direction(self.ndc([x, y]), aspect)
+                        {
+                            image.look_at(lon, lat);
+                            self.animation = None;
+                            self.rot_velocity = [0.0, 0.0, 0.0];
+                            self.rotated = true;
+                        }
+                    } else if let Some(index) = hotspot {
+                        actions.push(Action::FollowHotspot(index));
+                    } else if pressed
+                        && image
+                            .compare
+                            .as_ref()
+                            .is_some_and(|c| c.near_divider(x, self.window_size[0]))
+                    {
+                        self.dragging_divider = true;
+                    } else if pressed {
+                        self.dragging = true;
+                        self.drag_velocity = [0.0, 0.0, 0.0];
+                        self.rot_velocity = [0.0, 0.0, 0.0];
+                        self.last_drag_time = timestamp;
+                    } else if self.dragging_divider {
+                        self.dragging_divider = false;
+                    } else if self.dragging {
+                        self.dragging = false;
+                        if timestamp.wrapping_sub(self.last_drag_time) <= FLICK_WINDOW_MS {
+                            self.rot_velocity = self.drag_velocity;
+                        }
+                    }
+                }
+                Event::MouseButton {
+                    button: MOUSE_BUTTON_RIGHT,
+                    pressed: true,
+                    x,
+                    y,
+                    ..
+                } if annotations.editing.is_none() && image.single_view() => {
+                    if let Some(ray) = image.screen_ray(self.ndc([x, y])) {
+                        let at = Direction::from_vector(ray);
+                        if unsafe { SDL_GetModState() }.0 & KMOD_SHIFT.0 != 0 {
+                            annotations.add_corner(at);
+                        } else {
+                            annotations.add_point(at);
+                        }
+                    }
+                }
+                Event::TextInput { text, .. } => annotations.type_text(&text),
+                Event::Key {
+                    pressed: true,
+                    keycode,
+                    ..
+                } if annotations.editing.is_some() => {
+                    if keycode == SDLK_BACKSPACE {
+                        annotations.backspace();
+                    } else if keycode == SDLK_RETURN || keycode == SDLK_KP_ENTER {
+                        annotations.commit();
+                    } else if keycode == SDLK_ESCAPE {
+                        annotations.cancel();
+                    }
+                }
+                Event::Key { .. } if annotations.editing.is_some() => {}
+                // Enter closes a region being drawn and Escape abandons it.
+                Event::Key {
+                    pressed: true,
+                    repeat: 0,
+                    keycode,
+                    ..
+                } if !annotations.pending_region.is_empty()
+                    && (keycode == SDLK_RETURN
+                        || keycode == SDLK_KP_ENTER
+                        || keycode == SDLK_ESCAPE) =>
+                {
+                    if keycode == SDLK_ESCAPE {
+                        annotations.pending_region.clear();
+                    } else {
+                        annotations.finish_region();
+                    }
+                }
+                Event::MouseMotion { x_win, .. } if self.dragging_divider => {
+                    if let Some(compare) = &mut image.compare {
+                        compare.set_divider(x_win, self.window_size[0]);
+                    }
+                }
+                Event::MouseMotion {
+                    x_delta, y_delta, ..
+                } if self.dragging => {
+                    // Scale by the field of view so the scene tracks the cursor.
+                    let radians_per_pixel = image.fov / self.window_size[1] as f32;
+                    let angle_x = x_delta as f32 * radians_per_pixel;
+                    let angle_y = y_delta as f32 * radians_per_pixel;
+                    image.rotate_viewrays(angle_x, angle_y, 0.0);
+                    self.rotated = true;
+
+                    let elapsed_ms = timestamp.wrapping_sub(self.last_drag_time).max(1);
+                    let seconds = elapsed_ms as f32 / 1000.0;
+                    let blend = (seconds / 0.05).min(1.0);
+                    self.drag_velocity[0] += (angle_x / seconds - self.drag_velocity[0]) * blend;
+                    self.drag_velocity[1] += (angle_y / seconds - self.drag_velocity[1]) * blend;
+                    self.last_drag_time = timestamp;
+                }
+                Event::Key {
+                    pressed,
+                    repeat: 0,
+                    keycode,
+                    scancode,
+                    modifiers,
+                    ..
+                } => {
+                    let held = if pressed { 1.0 } else { -1.0 };
+                    match self.bindings.action(keycode, scancode) {
+                        Some(KeyAction::LookLeft) => self.rot_input[0] += held,
+                        Some(KeyAction::LookRight) => self.rot_input[0] -= held,
+                        Some(KeyAction::LookUp) => self.rot_input[1] += held,
+                        Some(KeyAction::LookDown) => self.rot_input[1] -= held,
+                        Some(KeyAction::RollLeft) => self.rot_input[2] += held,
+                        Some(KeyAction::RollRight) => self.rot_input[2] -= held,
+                        Some(KeyAction::ZoomIn) => self.fov_input -= held,
+                        Some(KeyAction::ZoomOut) => self.fov_input += held,
+                        Some(KeyAction::SourceScaleDown) => self.zoom_input -= held,
+                        Some(KeyAction::SourceScaleUp) => self.zoom_input += held,
+                        Some(KeyAction::ToggleFreeLook) if pressed => {
+                            image.free_look = !image.free_look
+                        }
+                        Some(KeyAction::ToggleLevelHorizon) if pressed => {
+                            image.level_horizon = !image.level_horizon;
+                            image.rotate_viewrays(0.0, 0.0, 0.0);
+                            self.rotated = true;
+                        }
+                        Some(KeyAction::NextProjection) if pressed => {
+                            image.set_projection(image.projection.next());
+                            println!("Projection: {}", image.projection.name());
+                            self.rotated = true;
+                        }
+                        Some(KeyAction::ToggleOverview) if pressed => {
+                            image.overview = !image.overview;
+                            self.dragging = false;
+                            self.rotated = true;
+                        }
+                        Some(KeyAction::ToggleGrid) if pressed => {
+                            image.overlays.grid = !image.overlays.grid
+                        }
+                        Some(KeyAction::ToggleHorizon) if pressed => {
+                            image.overlays.horizon = !image.overlays.horizon
+                        }
+                        Some(KeyAction::ToggleCompass) if pressed => {
+                            image.overlays.compass = !image.overlays.compass
+                        }
+                        Some(KeyAction::ToggleCompareLayout) if pressed => {
+                            if let Some(compare) = &mut image.compare {
+                                compare.toggle_layout();
+                                self.dragging_divider = false;
+                            }
+                        }
+                        Some(KeyAction::NextStereoOutput) if pressed => {
+                            image.stereo.output = image.stereo.output.next();
+                            println!("Stereo output: {:?}", image.stereo.output);
+                        }
+                        Some(KeyAction::IpdDown) if pressed => image.stereo.change_ipd(-0.01),
+                        Some(KeyAction::IpdUp) if pressed => image.stereo.change_ipd(0.01),
+                        Some(KeyAction::ConvergenceDown) if pressed => {
+                            image.stereo.change_convergence(-0.1f32.to_radians())
+                        }
+                        Some(KeyAction::ConvergenceUp) if pressed => {
+                            image.stereo.change_convergence(0.1f32.to_radians())
+                        }
+                        Some(KeyAction::PanniniCompressionDown) if pressed => {
+                            image.change_pannini_compression(-0.1);
+                            self.rotated = true;
+                        }
+                        Some(KeyAction::PanniniCompressionUp) if pressed => {
+                            image.change_pannini_compression(0.1);
+                            self.rotated = true;
+                        }
+                        Some(KeyAction::DeleteAnnotation) if pressed => {
+                            if let Some(index) = self.annotation_under_mouse(image, annotations) {
+                                annotations.remove(index);
+                            }
+                        }
+                        Some(KeyAction::RenameAnnotation) if pressed => {
+                            if let Some(index) = self.annotation_under_mouse(image, annotations) {
+                                annotations.rename(index);
+                            }
+                        }
+                        Some(KeyAction::AddKeyframe) if pressed => {
+                            actions.push(Action::AddKeyframe)
+                        }
+                        Some(KeyAction::ClearPath) if pressed => actions.push(Action::ClearPath),
+                        Some(KeyAction::PlayPath) if pressed => actions.push(Action::PlayPath),
+                        Some(KeyAction::ToggleAutoRotate) if pressed => {
+                            self.auto_rotate.enabled = !self.auto_rotate.enabled;
+                            let state = if self.auto_rotate.enabled {
+                                "on"
+                            } else {
+                                "off"
+                            };
+                            println!("Auto-rotate: {}", state);
+                        }
+                        Some(KeyAction::PreviousImage) if pressed => {
+                            actions.push(Action::PreviousImage)
+                        }
+                        Some(KeyAction::NextImage) if pressed => actions.push(Action::NextImage),
+                        Some(KeyAction::ToggleHud) if pressed => actions.push(Action::ToggleHud),
+                        Some(KeyAction::ToggleMinimap) if pressed => {
+                            actions.push(Action::ToggleMinimap)
+                        }
+                        Some(KeyAction::ToggleHelp) if pressed => actions.push(Action::ToggleHelp),
+                        Some(KeyAction::Exit) => {
+                            actions.push(Action::Exit);
+                            break;
+                        }
+                        // Number keys left unbound hold bookmarks.
+                        None if pressed => {
+                            if let Some(slot) = bookmark_slot(keycode) {
+                                actions.push(if modifiers.0 & KMOD_CTRL.0 != 0 {
+                                    Action::SaveBookmark(slot)
+                                } else {
+                                    Action::RecallBookmark(slot)
+                                });
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(Animation::Showcase { elapsed, .. }) = &self.animation {
+            if self.auto_rotate.gallery_due(*elapsed) {
+                self.animation = None;
+                actions.push(Action::NextImage);
+            }
+        }
+        if annotations.editing.is_some() != self.text_input {
+            self.text_input = !self.text_input;
+            // Keys released while typing never reach the bindings, so forget
+            // whatever was held on either side of the label.
+            self.rot_input = [0.0, 0.0, 0.0];
+            self.fov_input = 0.0;
+            self.zoom_input = 0.0;
+            unsafe {
+                if self.text_input {
+                    SDL_StartTextInput();
+                } else {
+                    SDL_StopTextInput();
+                }
+            }
+        }
+        actions
+    }
+
+    /// Starts an eased move from the current view to `pose`.
+    pub fn glide_to(&mut self, image: &RotImage, pose: Pose) {
+        self.start(Animation::Glide(Glide::new(
+            image.pose(),
+            pose,
+            GLIDE_SECONDS,
+        )));
+    }
+
+    /// Starts playing a camera path from its first keyframe.
+    pub fn play(&mut self, path: CameraPath) {
+        self.start(Animation::Path { path, elapsed: 0.0 });
+    }
+
+    pub fn playing_path(&self) -> bool {
+        matches!(self.animation, Some(Animation::Path { .. }))
+    }
+
+    /// Stops any bookmark glide, path playback or idle spin where it is.
+    pub fn stop_animation(&mut self) {
+        self.animation = None;
+    }
+
+    fn start(&mut self, animation: Animation) {
+        self.animation = Some(animation);
+        self.rot_velocity = [0.0, 0.0, 0.0];
+        self.fov_velocity = 0.0;
+        self.zoom_velocity = 0.0;
+    }
+
+    /// Notes that someone is using the viewer, which stops the idle spin.
+    fn wake(&mut self) {
+        self.idle = 0.0;
+        if matches!(self.animation, Some(Animation::Showcase { .. })) {
+            self.animation = None;
+        }
+    }
+
+    /// Maps a point in screen coordinates to the window's -1 to 1 range, y up.
+    fn ndc(&self, [x, y]: [i32; 2]) -> [f32; 2] {
+        let [width, height] = self.window_size;
+        [
+            2.0 * x as f32 / width as f32 - 1.0,
+            1.0 - 2.0 * y as f32 / height as f32,
+        ]
+    }
+
+    /// Gets the direction under a point in screen coordinates, along with how
+    /// far from it in radians something on screen can be picked.
+    fn pick(&self, image: &RotImage, point: [i32; 2]) -> Option<(Vec3, f32)> {
+        if !image.single_view() {
+            return None;
+        }
+        let ray = image.screen_ray(self.ndc(point))?;
+        let radians_per_pixel = image.fov / self.window_size[1] as f32;
+        Some((ray, PICK_RADIUS * radians_per_pixel))
+    }
+
+    /// Finds the annotation nearest the cursor, if one is close enough to pick.
+    fn annotation_under_mouse(&self, image: &RotImage, annotations: &Annotations) -> Option<usize> {
+        let (ray, radius) = self.pick(image, self.mouse)?;
+        annotations.nearest(ray, radius)
+    }
+
+    /// Advances camera motion by `dt` seconds, returning `true` if the view changed.
+    pub fn update(&mut self, dt: f32, image: &mut RotImage) -> bool {
+        let mut changed = std::mem::take(&mut self.rotated);
+
+        let [left_x, left_y] = self.apply_stick_dead_zone(self.sticks[0], self.sticks[1]);
+        let [right_x, right_y] = self.apply_stick_dead_zone(self.sticks[2], self.sticks[3]);
+        // Stick y is positive downwards, so pushing up looks up.
+        let analog_rot = [-(left_x + right_x), -(left_y + right_y), 0.0];
+        let analog_fov = self.apply_trigger_dead_zone(self.triggers[0])
+            - self.apply_trigger_dead_zone(self.triggers[1]);
+
+        let steering = self.dragging
+            || self.rot_input.iter().chain(&analog_rot).any(|&v| v != 0.0)
+            || self.fov_input + analog_fov != 0.0
+            || self.zoom_input != 0.0;
+        // Held sticks send no events, so they keep the viewer awake here.
+        if steering {
+            self.wake();
+        } else {
+            self.idle += dt;
+        }
+        let at_rest = self.rot_velocity.iter().all(|&v| v == 0.0)
+            && self.fov_velocity == 0.0
+            && self.zoom_velocity == 0.0;
+        if self.auto_rotate.enabled
+            && self.animation.is_none()
+            && self.idle >= self.auto_rotate.idle_timeout
+            && at_rest
+            && !self.text_input
+        {
+            self.animation = Some(Animation::Showcase {
+                from: image.pose(),
+                elapsed: 0.0,
+            });
+        }
+
+        if let Some(animation) = &mut self.animation {
+            if steering {
+                self.animation = None;
+            } else {
+                let (pose, finished) = match animation {
+                    Animation::Glide(glide) => (Some(glide.advance(dt)), glide.finished()),
+                    Animation::Path { path, elapsed } => {
+                        *elapsed += dt;
+                        (path.pose_at(*elapsed), *elapsed >= path.duration())
+                    }
+                    Animation::Showcase { from, elapsed } => {
+                        *elapsed += dt;
+                        (Some(self.auto_rotate.pose_at(from, *elapsed)), false)
+                    }
+                };
+                if let Some(pose) = pose {
+                    image.set_pose(pose);
+                }
+                if finished {
+                    self.animation = None;
+                }
+                return true;
+            }
+        }
+
+        if !self.dragging {
+            self.rot_velocity = std::array::from_fn(|axis| {
+                let input = (self.rot_input[axis] + analog_rot[axis]).clamp(-1.0, 1.0);
+                self.approach(
+                    self.rot_velocity[axis],
+                    input * self.camera_rot_speed,
+                    self.camera_rot_speed,
+                    dt,
+                )
+            });
+        }
+        let fov_input = (self.fov_input + analog_fov).clamp(-1.0, 1.0);
+        self.fov_velocity = self.approach(
+            self.fov_velocity,
+            fov_input * self.fov_scale_speed,
+            self.fov_scale_speed,
+            dt,
+        );
+        self.zoom_velocity = self.approach(
+            self.zoom_velocity,
+            self.zoom_input * self.zoom_scale_speed,
+            self.zoom_scale_speed,
+            dt,
+        );
+
+        if self.rot_velocity.iter().any(|&v| v != 0.0)
+            || self.fov_velocity != 0.0
+            || self.zoom_velocity != 0.0
+        {
+            changed = true;
+            image.change_fov(self.fov_velocity * dt);
+            image.change_zoom(self.zoom_velocity * dt);
+            image.rotate_viewrays(
+                self.rot_velocity[0] * dt,
+                self.rot_velocity[1] * dt,
+                self.rot_velocity[2] * dt,
+            );
+        }
+        changed
+    }
+
+    /// Zeroes a stick inside the radial dead zone and rescales the rest of
+    /// its travel back to the full `0.0..=1.0` range.
+    fn apply_stick_dead_zone(&self, x: f32, y: f32) -> [f32; 2] {
+        let magnitude = (x * x + y * y).sqrt();
+        if magnitude <= self.stick_dead_zone {
+            return [0.0, 0.0];
+        }
+        let scaled = ((magnitude - self.stick_dead_zone) / (1.0 - self.stick_dead_zone)).min(1.0);
+        [x / magnitude * scaled, y / magnitude * scaled]
+    }
+
+    fn apply_trigger_dead_zone(&self, value: f32) -> f32 {
+        if value <= self.trigger_dead_zone {
+            0.0
+        } else {
+            ((value - self.trigger_dead_zone) / (1.0 - self.trigger_dead_zone)).min(1.0)
+        }
+    }
+
+    /// Accelerates `velocity` towards a held `target`, or lets it decay to rest
+    /// when nothing is held.
+    fn approach(&self, velocity: f32, target: f32, top_speed: f32, dt: f32) -> f32 {
+        if target != 0.0 {
+            let step = self.acceleration * top_speed * dt;
+            velocity + (target - velocity).clamp(-step, step)
+        } else {
+            let decayed = velocity * (-self.damping * dt).exp();
+            if decayed.abs() < REST_EPSILON * top_speed.max(1.0) {
+                0.0
+            } else {
+                decayed
+            }
+        }
+    }
+}
+
+/// Gets the bookmark a number key holds, 1 to 9.
+fn bookmark_slot(keycode: SDL_Keycode) -> Option<u8> {
+    if (SDLK_1.0..=SDLK_9.0).contains(&keycode.0) {
+        Some((keycode.0 - SDLK_0.0) as u8)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn controller(stick_dead_zone: f32, trigger_dead_zone: f32) -> CameraController {
+        let mut config = Config::default();
+        config.gamepad.stick_dead_zone = stick_dead_zone;
+        config.gamepad.trigger_dead_zone = trigger_dead_zone;
+        CameraController::new(&config)
+    }
+
+    fn length([x, y]: [f32; 2]) -> f32 {
+        x.hypot(y)
+    }
+
+    #[test]
+    fn sticks_inside_the_dead_zone_are_still() {
+        let controller = controller(0.2, 0.05);
+        for [x, y] in [[0.0, 0.0], [0.1, 0.0], [-0.1, 0.1], [0.0, -0.2]] {
+            assert_eq!(controller.apply_stick_dead_zone(x, y), [0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn sticks_at_full_deflection_are_at_full_speed() {
+        let controller = controller(0.2, 0.05);
+        let diagonal = std::f32::consts::FRAC_1_SQRT_2;
+        for [x, y] in [[1.0, 0.0], [0.0, -1.0], [diagonal, diagonal], [1.0, 1.0]] {
+            let [sx, sy] = controller.apply_stick_dead_zone(x, y);
+            assert!((length([sx, sy]) - 1.0).abs() < 1e-5, "{:?}", [sx, sy]);
+            // The direction is kept.
+            assert!((sx * y - sy * x).abs() < 1e-5, "{:?}", [sx, sy]);
+        }
+    }
+
+    #[test]
+    fn sticks_pick_up_smoothly_past_the_dead_zone() {
+        let controller = controller(0.2, 0.05);
+        let just_past = controller.apply_stick_dead_zone(0.2 + 1e-4, 0.0);
+        assert!(length(just_past) < 1e-3, "{:?}", just_past);
+        let mut last = 0.0;
+        for step in 0..=100 {
+            let magnitude = step as f32 / 100.0;
+            let scaled = length(controller.apply_stick_dead_zone(0.0, magnitude));
+            assert!(
+                scaled >= last && scaled - last < 0.02,
+                "{} at {}",
+                scaled,
+                magnitude
+            );
+            last = scaled;
+        }
+    }
+
+    #[test]
+    fn triggers_skip_the_dead_zone_and_reach_full_travel() {
+        let controller = controller(0.2, 0.05);
+        assert_eq!(controller.apply_trigger_dead_zone(0.0), 0.0);
+        assert_eq!(controller.apply_trigger_dead_zone(0.05), 0.0);
+        assert!(controller.apply_trigger_dead_zone(0.05 + 1e-4) < 1e-3);
+        assert_eq!(controller.apply_trigger_dead_zone(1.0), 1.0);
+        let mut last = 0.0;
+        for step in 0..=100 {
+            let value = controller.apply_trigger_dead_zone(step as f32 / 100.0);
+            assert!(
+                value >= last && value - last < 0.02,
+                "{} at {}",
+                value,
+                step
+            );
+            last = value;
+        }
+    }
+}
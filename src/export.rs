@@ -0,0 +1,411 @@
+//! Renders camera motion offscreen at a fixed frame rate and saves the frames
+//! as numbered PNGs, an animated GIF or an animated PNG, or checks them
+//! against golden images.
+
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, RgbaImage,
+};
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use crate::gl_safe::{ColorFormat, Context, Framebuffer, Renderbuffer, Texture};
+use crate::pose::Pose;
+use crate::renderer::Renderer;
+use crate::RotImage;
+
+/// How hard the GIF encoder works on each frame's palette, 1 (best) to 30.
+const GIF_SPEED: i32 = 10;
+/// Largest difference in any channel that still counts as the same pixel,
+/// allowing for rounding differences between GL implementations.
+const GOLDEN_TOLERANCE: u8 = 2;
+/// Share of pixels that may differ by more, such as along edges that
+/// rasterise a little differently.
+const GOLDEN_MAX_DIFFERING: f32 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// `frame_00000.png` and so on in a directory.
+    Frames,
+    Gif,
+    Apng,
+    /// Compares each frame with `frame_00000.png` and so on in a directory.
+    /// A missing golden image counts as a mismatch.
+    Golden,
+    /// Saves the frames as a directory's golden images, clearing out the
+    /// `.actual.png` frames of earlier checks and any golden images past the
+    /// last frame.
+    GoldenUpdate,
+}
+
+/// Where and how to save an export.
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub target: PathBuf,
+    pub format: Format,
+    pub fps: f32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Export {
+    /// Picks the format from the target: `.gif` files are GIFs, `.apng` and
+    /// `.png` files are APNGs, and a path with no extension is a directory for
+    /// numbered PNGs. Any other extension is an error.
+    pub fn new(target: &str, fps: f32, width: u32, height: u32) -> Result<Self, String> {
+        let target = PathBuf::from(target);
+        let extension = target
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase());
+        let format = match extension.as_deref() {
+            Some("gif") => Format::Gif,
+            Some("apng") | Some("png") => Format::Apng,
+            None => Format::Frames,
+            Some(extension) => {
+                return Err(format!(
+                    "can't export to a .{} file; use .gif, .apng or .png, or a path with no extension for a directory of frames",
+                    extension
+                ))
+            }
+        };
+        Ok(Self {
+            target,
+            format,
+            fps,
+            width,
+            height,
+        })
+    }
+
+    /// Draws `duration` seconds of camera motion, with `pose_at` placing the
+    /// camera at each frame's time, and saves every frame. Returns how many
+    /// frames were saved.
+    pub fn render(
+        &self,
+        gl: &Context,
+        duration: f32,
+        pose_at: impl Fn(f32) -> Pose,
+        image: &mut RotImage,
+        renderer: &Renderer,
+        texture: &Texture,
+    ) -> Result<usize, String> {
+        let frame_count = (duration * self.fps).floor() as usize + 1;
+        let mut sink = Sink::open(self, frame_count)?;
+
+        let framebuffer = Framebuffer::new(gl).ok_or("Couldn't make a framebuffer")?;
+        let target = Renderbuffer::new(gl, self.width, self.height, ColorFormat::Rgba8)
+            .ok_or("Couldn't make a renderbuffer")?;
+        framebuffer.attach_renderbuffer(0, &target);
+        framebuffer.read_from(0);
+        if let Err(e) = framebuffer.check() {
+            Framebuffer::clear_binding();
+            return Err(e);
+        }
+
+        let region = [0, 0, self.width as i32, self.height as i32];
+        let result = (0..frame_count).try_for_each(|index| {
+            image.set_pose(pose_at(index as f32 / self.fps));
+            renderer.upload_viewrays(image);
+            gl.clear();
+            renderer.draw(image, &image.source, texture, region, region, 1.0)?;
+            sink.push(gl.read_pixels(self.width, self.height))
+        });
+        Framebuffer::clear_binding();
+        result?;
+        sink.finish()?;
+        Ok(frame_count)
+    }
+}
+
+/// Where frames go as they're rendered.
+enum Sink {
+    Frames {
+        dir: PathBuf,
+        written: usize,
+    },
+    Gif {
+        encoder: GifEncoder<File>,
+        delay: Delay,
+    },
+    Apng(png::Writer<BufWriter<File>>),
+    Golden {
+        dir: PathBuf,
+        compared: usize,
+        mismatches: Vec<String>,
+    },
+}
+
+impl Sink {
+    fn open(export: &Export, frame_count: usize) -> Result<Self, String> {
+        let error = |e: &dyn std::fmt::Display| format!("{}: {}", export.target.display(), e);
+        let fps = export.fps.round().max(1.0) as u32;
+        match export.format {
+            Format::Frames => {
+                fs::create_dir_all(&export.target).map_err(|e| error(&e))?;
+                Ok(Sink::Frames {
+                    dir: export.target.clone(),
+                    written: 0,
+                })
+            }
+            Format::Gif => {
+                let file = File::create(&export.target).map_err(|e| error(&e))?;
+                let mut encoder = GifEncoder::new_with_speed(file, GIF_SPEED);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|e| error(&e))?;
+                Ok(Sink::Gif {
+                    encoder,
+                    delay: Delay::from_numer_denom_ms(1000, fps),
+                })
+            }
+            Format::Apng => {
+                let file = File::create(&export.target).map_err(|e| error(&e))?;
+                let mut encoder =
+                    png::Encoder::new(BufWriter::new(file), export.width, export.height);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder
+                    .set_animated(frame_count as u32, 0)
+                    .map_err(|e| error(&e))?;
+                encoder
+                    .set_frame_delay(1, fps.min(u16::MAX as u32) as u16)
+                    .map_err(|e| error(&e))?;
+                let writer = encoder.write_header().map_err(|e| error(&e))?;
+                Ok(Sink::Apng(writer))
+            }
+            Format::Golden => {
+                if !export.target.is_dir() {
+                    return Err(error(
+                        &"there's no such golden image directory; record one with --golden-update",
+                    ));
+                }
+                Ok(Sink::Golden {
+                    dir: export.target.clone(),
+                    compared: 0,
+                    mismatches: Vec::new(),
+                })
+            }
+            Format::GoldenUpdate => {
+                fs::create_dir_all(&export.target).map_err(|e| error(&e))?;
+                remove_stale_golden(&export.target, frame_count).map_err(|e| error(&e))?;
+                Ok(Sink::Frames {
+                    dir: export.target.clone(),
+                    written: 0,
+                })
+            }
+        }
+    }
+
+    fn push(&mut self, frame: RgbaImage) -> Result<(), String> {
+        match self {
+            Sink::Frames { dir, written } => {
+                let path = frame_path(dir, *written);
+                *written += 1;
+                frame
+                    .save(&path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            Sink::Gif { encoder, delay } => encoder
+                .encode_frame(Frame::from_parts(frame, 0, 0, *delay))
+                .map_err(|e| e.to_string()),
+            Sink::Apng(writer) => writer
+                .write_image_data(frame.as_raw())
+                .map_err(|e| e.to_string()),
+            Sink::Golden {
+                dir,
+                compared,
+                mismatches,
+            } => {
+                let path = frame_path(dir, *compared);
+                *compared += 1;
+                let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+                // Keep what was drawn next to the golden image to look at.
+                let actual = path.with_extension("actual.png");
+                let difference = if path.exists() {
+                    let golden = image::open(&path).map_err(|e| error(&e))?.into_rgba8();
+                    golden_difference(&golden, &frame)
+                } else {
+                    Some("there's no golden image; record one with --golden-update".to_string())
+                };
+                match difference {
+                    Some(difference) => {
+                        frame.save(&actual).map_err(|e| error(&e))?;
+                        mismatches.push(format!(
+                            "{}: {}, drawn as {}",
+                            path.display(),
+                            difference,
+                            actual.display()
+                        ));
+                    }
+                    None => {
+                        let _ = fs::remove_file(&actual);
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self {
+            Sink::Apng(writer) => writer.finish().map_err(|e| e.to_string()),
+            Sink::Golden { mismatches, .. } if !mismatches.is_empty() => Err(format!(
+                "{} frames don't match their golden images:\n{}",
+                mismatches.len(),
+                mismatches.join("\n")
+            )),
+            Sink::Golden { .. } => Ok(()),
+            // Dropping the GIF encoder writes its trailer.
+            Sink::Frames { .. } | Sink::Gif { .. } => Ok(()),
+        }
+    }
+}
+
+fn frame_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("frame_{:05}.png", index))
+}
+
+/// Deletes the `.actual.png` frames in a golden image directory, and the
+/// golden images from `frame_count` on.
+fn remove_stale_golden(dir: &Path, frame_count: usize) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(index) = name
+            .strip_prefix("frame_")
+            .and_then(|name| name.strip_suffix(".png"))
+        else {
+            continue;
+        };
+        let stale = match index.strip_suffix(".actual") {
+            Some(_) => true,
+            None => index.parse::<usize>().is_ok_and(|i| i >= frame_count),
+        };
+        if stale {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Says how `frame` differs from `golden`, or `None` if it's close enough.
+fn golden_difference(golden: &RgbaImage, frame: &RgbaImage) -> Option<String> {
+    if golden.dimensions() != frame.dimensions() {
+        let (width, height) = golden.dimensions();
+        return Some(format!(
+            "the golden image is {}x{} but the frame is {}x{}",
+            width,
+            height,
+            frame.width(),
+            frame.height()
+        ));
+    }
+    let differing = golden
+        .pixels()
+        .zip(frame.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0)
+                .any(|(&a, b)| a.abs_diff(b) > GOLDEN_TOLERANCE)
+        })
+        .count();
+    let share = differing as f32 / (frame.width() * frame.height()).max(1) as f32;
+    (share > GOLDEN_MAX_DIFFERING).then(|| format!("{:.2}% of pixels differ", share * 100.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    const GREY: Rgba<u8> = Rgba([100, 100, 100, 255]);
+
+    #[test]
+    fn channels_within_the_tolerance_match() {
+        let golden = RgbaImage::from_pixel(10, 10, GREY);
+        let [r, g, b, a] = GREY.0;
+        let frame = RgbaImage::from_pixel(
+            10,
+            10,
+            Rgba([r + GOLDEN_TOLERANCE, g - GOLDEN_TOLERANCE, b, a]),
+        );
+        assert_eq!(golden_difference(&golden, &frame), None);
+    }
+
+    #[test]
+    fn a_channel_past_the_tolerance_differs() {
+        let golden = RgbaImage::from_pixel(10, 10, GREY);
+        let [r, g, b, a] = GREY.0;
+        let frame = RgbaImage::from_pixel(10, 10, Rgba([r, g, b + GOLDEN_TOLERANCE + 1, a]));
+        assert_eq!(
+            golden_difference(&golden, &frame).as_deref(),
+            Some("100.00% of pixels differ")
+        );
+    }
+
+    #[test]
+    fn only_a_sliver_of_pixels_may_differ() {
+        // One pixel in a thousand is the most that may differ.
+        let golden = RgbaImage::from_pixel(100, 10, GREY);
+        let mut frame = golden.clone();
+        frame.put_pixel(0, 0, Rgba([255; 4]));
+        assert_eq!(golden_difference(&golden, &frame), None);
+        frame.put_pixel(1, 0, Rgba([255; 4]));
+        assert_eq!(
+            golden_difference(&golden, &frame).as_deref(),
+            Some("0.20% of pixels differ")
+        );
+    }
+
+    #[test]
+    fn picks_the_format_from_the_extension() {
+        let format = |target| Export::new(target, 30.0, 64, 48).map(|e| e.format);
+        assert_eq!(format("spin.gif"), Ok(Format::Gif));
+        assert_eq!(format("spin.GIF"), Ok(Format::Gif));
+        assert_eq!(format("spin.apng"), Ok(Format::Apng));
+        assert_eq!(format("spin.png"), Ok(Format::Apng));
+        assert_eq!(format("frames"), Ok(Format::Frames));
+        assert_eq!(format("out/frames/"), Ok(Format::Frames));
+        assert!(format("spin.mp4").is_err());
+        assert!(format("spin.jpg").is_err());
+    }
+
+    #[test]
+    fn updating_removes_stale_golden_frames() {
+        let dir = std::env::temp_dir().join(format!("golden-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let names = [
+            "frame_00000.png",
+            "frame_00001.png",
+            "frame_00002.png",
+            "frame_00000.actual.png",
+            "notes.txt",
+        ];
+        for name in names {
+            fs::write(dir.join(name), b"").unwrap();
+        }
+        remove_stale_golden(&dir, 2).unwrap();
+        let mut left: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        left.sort();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(left, ["frame_00000.png", "frame_00001.png", "notes.txt"]);
+    }
+
+    #[test]
+    fn sizes_must_match() {
+        let golden = RgbaImage::from_pixel(4, 3, GREY);
+        let frame = RgbaImage::from_pixel(3, 4, GREY);
+        assert_eq!(
+            golden_difference(&golden, &frame).as_deref(),
+            Some("the golden image is 4x3 but the frame is 3x4")
+        );
+    }
+}
@@ -1,38 +1,381 @@
-use std::{convert::TryInto, ffi::c_uint, path::Path};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    ffi::{c_uint, c_void, CStr, CString},
+    marker::PhantomData,
+    mem::size_of,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use beryllium::video::GlWindow;
 
 use gl33::{
-    gl_enumerations::{GL_INFO_LOG_LENGTH, GL_LINK_STATUS, GL_TEXTURE_2D, GL_TRUE},
+    gl_enumerations::{
+        GL_BLEND, GL_CLAMP_TO_EDGE, GL_COLOR_ATTACHMENT0, GL_COLOR_BUFFER_BIT,
+        GL_COLOR_CLEAR_VALUE, GL_DEBUG_OUTPUT, GL_DEBUG_OUTPUT_SYNCHRONOUS,
+        GL_DEBUG_SEVERITY_NOTIFICATION, GL_DEBUG_TYPE_ERROR, GL_DEBUG_TYPE_PERFORMANCE,
+        GL_DEPTH_BUFFER_BIT, GL_EXTENSIONS, GL_FLOAT, GL_FRAMEBUFFER, GL_FRAMEBUFFER_COMPLETE,
+        GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT, GL_FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER,
+        GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT, GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE,
+        GL_FRAMEBUFFER_INCOMPLETE_READ_BUFFER, GL_FRAMEBUFFER_UNSUPPORTED, GL_INFO_LOG_LENGTH,
+        GL_INVALID_ENUM, GL_INVALID_FRAMEBUFFER_OPERATION, GL_INVALID_OPERATION, GL_INVALID_VALUE,
+        GL_LINK_STATUS, GL_NEAREST, GL_NONE, GL_NO_ERROR, GL_NUM_EXTENSIONS,
+        GL_ONE_MINUS_SRC_ALPHA, GL_OUT_OF_MEMORY, GL_PACK_ALIGNMENT, GL_RENDERBUFFER, GL_RENDERER,
+        GL_RGBA16F, GL_RGBA8, GL_SCISSOR_TEST, GL_SRC_ALPHA, GL_SRGB8_ALPHA8, GL_TEXTURE0,
+        GL_TEXTURE_2D, GL_TRIANGLES, GL_TRIANGLE_FAN, GL_TRUE,
+    },
     global_loader::*,
     GLenum, GL_ARRAY_BUFFER, GL_COMPILE_STATUS, GL_ELEMENT_ARRAY_BUFFER, GL_FILL,
     GL_FRAGMENT_SHADER, GL_FRONT_AND_BACK, GL_LINE, GL_LINEAR, GL_LINEAR_MIPMAP_LINEAR, GL_POINT,
     GL_REPEAT, GL_RGBA, GL_TEXTURE_MAG_FILTER, GL_TEXTURE_MIN_FILTER, GL_TEXTURE_WRAP_S,
     GL_TEXTURE_WRAP_T, GL_UNSIGNED_BYTE, GL_VERTEX_SHADER,
 };
-use image::{EncodableLayout, ImageError};
+use image::{imageops, EncodableLayout, ImageError, RgbaImage};
+use ultraviolet::{Mat3, Vec2, Vec3, Vec4};
+
+/// Proof that a GL context is current on this thread and its functions are
+/// loaded. Every GL object borrows it, so none can outlive the context.
+pub struct Context<'win> {
+    /// The window, or other owner of the context, that must outlive it.
+    _owner: PhantomData<&'win ()>,
+    /// A context is only current on the thread that made it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'win> Context<'win> {
+    /// Loads the functions of `window`'s context, which SDL made current when
+    /// it opened the window.
+    ///
+    /// With `debug` set, every `gl_safe` call is checked for GL errors, which
+    /// are printed along with the call that raised them.
+    pub fn load(window: &'win GlWindow, debug: bool) -> Self {
+        unsafe { Self::load_with(window, &|name| window.get_proc_address(name), debug) }
+    }
+
+    /// Loads the functions of the context current on this thread, looking
+    /// them up with `get_proc_address`, like [`load`](Self::load) does for a
+    /// window.
+    ///
+    /// # Safety
+    ///
+    /// A context must be current on this thread and stay current for as
+    /// long as `owner` is borrowed.
+    pub unsafe fn load_with<T>(
+        _owner: &'win T,
+        get_proc_address: &dyn Fn(*const u8) -> *const c_void,
+        debug: bool,
+    ) -> Self {
+        load_global_gl(get_proc_address);
+        if debug {
+            enable_debug_output();
+        }
+        Self {
+            _owner: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Names the GL implementation, e.g. to tell llvmpipe from a GPU.
+    pub fn renderer_name(&self) -> String {
+        let _checked = checked("Context::renderer_name");
+        let name = unsafe { glGetString(GL_RENDERER) };
+        if name.is_null() {
+            return "unknown renderer".to_string();
+        }
+        unsafe { CStr::from_ptr(name.cast()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    pub fn clear_color(&self, r: f32, g: f32, b: f32, a: f32) {
+        let _checked = checked("Context::clear_color");
+        unsafe { glClearColor(r, g, b, a) }
+    }
+
+    /// Clears the bound framebuffer to the clear colour.
+    pub fn clear(&self) {
+        let _checked = checked("Context::clear");
+        unsafe { glClear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT) }
+    }
+
+    /// Maps drawing onto an `[x, y, width, height]` pixel rectangle.
+    pub fn viewport(&self, rect: [i32; 4]) {
+        let _checked = checked("Context::viewport");
+        let [x, y, width, height] = rect;
+        unsafe { glViewport(x, y, width, height) }
+    }
+
+    /// Blends everything drawn over what's already there by its alpha.
+    pub fn enable_blending(&self) {
+        let _checked = checked("Context::enable_blending");
+        unsafe {
+            glBlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA);
+            glEnable(GL_BLEND);
+        }
+    }
+
+    /// Picks the texture unit that [`Texture::bind`] binds to, counting from 0.
+    pub fn active_texture(&self, unit: u32) {
+        let _checked = checked("Context::active_texture");
+        unsafe { glActiveTexture(GLenum(GL_TEXTURE0.0 + unit)) }
+    }
+
+    /// Limits drawing to an `[x, y, width, height]` pixel rectangle, or lifts
+    /// the limit with `None`.
+    pub fn scissor(&self, rect: Option<[i32; 4]>) {
+        let _checked = checked("Context::scissor");
+        unsafe {
+            match rect {
+                Some([x, y, width, height]) => {
+                    glEnable(GL_SCISSOR_TEST);
+                    glScissor(x, y, width, height);
+                }
+                None => glDisable(GL_SCISSOR_TEST),
+            }
+        }
+    }
 
-pub fn clear_color(r: f32, g: f32, b: f32, a: f32) {
-    unsafe { glClearColor(r, g, b, a) }
+    /// Picks which of the red, green, blue and alpha channels drawing writes.
+    pub fn color_mask(&self, mask: [bool; 4]) {
+        let _checked = checked("Context::color_mask");
+        let [r, g, b, a] = mask.map(u8::from);
+        unsafe { glColorMask(r, g, b, a) }
+    }
+
+    /// Draws `count` vertices of the bound vertex array, starting at `first`.
+    pub fn draw_arrays(&self, mode: Primitive, first: i32, count: i32) {
+        let _checked = checked("Context::draw_arrays");
+        unsafe { glDrawArrays(GLenum(mode as u32), first, count) }
+    }
+
+    /// Fills an `[x, y, width, height]` pixel rectangle with a solid colour,
+    /// leaving the clear colour as it was.
+    pub fn fill_rect(&self, rect: [i32; 4], color: [f32; 4]) {
+        let _checked = checked("Context::fill_rect");
+        let [x, y, width, height] = rect;
+        let [r, g, b, a] = color;
+        unsafe {
+            let mut previous = [0.0f32; 4];
+            glGetFloatv(GL_COLOR_CLEAR_VALUE, previous.as_mut_ptr());
+            glEnable(GL_SCISSOR_TEST);
+            glScissor(x, y, width, height);
+            glClearColor(r, g, b, a);
+            glClear(GL_COLOR_BUFFER_BIT);
+            glDisable(GL_SCISSOR_TEST);
+            let [r, g, b, a] = previous;
+            glClearColor(r, g, b, a);
+        }
+    }
+
+    /// Reads back the bottom left `width` by `height` pixels of the bound
+    /// framebuffer, turned the right way up.
+    pub fn read_pixels(&self, width: u32, height: u32) -> RgbaImage {
+        let _checked = checked("Context::read_pixels");
+        let mut pixels = RgbaImage::new(width, height);
+        unsafe {
+            glPixelStorei(GL_PACK_ALIGNMENT, 1);
+            glReadPixels(
+                0,
+                0,
+                width as i32,
+                height as i32,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                pixels.as_mut_ptr().cast(),
+            );
+        }
+        // GL stores the bottom row first.
+        imageops::flip_vertical_in_place(&mut pixels);
+        pixels
+    }
+}
+
+/// Ties a GL object to the [`Context`] it was made in.
+type Owner<'gl> = PhantomData<&'gl Context<'gl>>;
+
+/// Whether `gl_safe` calls are checked for errors.
+static DEBUG: AtomicBool = AtomicBool::new(false);
+/// Whether the driver reports errors through a `KHR_debug` callback as they
+/// happen, rather than them being polled with `glGetError`.
+static CALLBACK: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// The `gl_safe` call in progress, named in error reports.
+    static OPERATION: Cell<&'static str> = const { Cell::new("GL call outside gl_safe") };
+    /// The program last put in use by [`ShaderProgram::use_program`].
+    static PROGRAM_IN_USE: Cell<c_uint> = const { Cell::new(0) };
 }
 
-pub struct VertexArray(pub c_uint);
-impl VertexArray {
-    pub fn new() -> Option<Self> {
+/// Turns on error checking, through `KHR_debug` where the driver has it.
+fn enable_debug_output() {
+    DEBUG.store(true, Ordering::Relaxed);
+    if has_extension("GL_KHR_debug") && glDebugMessageCallback_is_loaded() {
+        unsafe {
+            glEnable(GL_DEBUG_OUTPUT);
+            // Report from inside the call that went wrong, so `OPERATION` is
+            // still set.
+            glEnable(GL_DEBUG_OUTPUT_SYNCHRONOUS);
+            glDebugMessageCallback(Some(report_debug_message), std::ptr::null());
+        }
+        CALLBACK.store(true, Ordering::Relaxed);
+        eprintln!("GL debug output on, through KHR_debug");
+    } else {
+        eprintln!("GL debug output on, KHR_debug is missing so checking glGetError");
+    }
+}
+
+fn has_extension(name: &str) -> bool {
+    let mut count = 0;
+    unsafe { glGetIntegerv(GL_NUM_EXTENSIONS, &mut count) };
+    (0..count.max(0) as u32).any(|i| {
+        let extension = unsafe { glGetStringi(GL_EXTENSIONS, i) };
+        !extension.is_null()
+            && unsafe { CStr::from_ptr(extension.cast()) }.to_bytes() == name.as_bytes()
+    })
+}
+
+/// Prints a `KHR_debug` message, leaving out chatty notifications.
+extern "system" fn report_debug_message(
+    _source: GLenum,
+    ty: GLenum,
+    _id: c_uint,
+    severity: GLenum,
+    length: i32,
+    message: *const u8,
+    _user: *const c_void,
+) {
+    if severity == GL_DEBUG_SEVERITY_NOTIFICATION || message.is_null() {
+        return;
+    }
+    let message = if length < 0 {
+        unsafe { CStr::from_ptr(message.cast()) }.to_bytes()
+    } else {
+        unsafe { std::slice::from_raw_parts(message, length as usize) }
+    };
+    let kind = match ty {
+        GL_DEBUG_TYPE_ERROR => "error",
+        GL_DEBUG_TYPE_PERFORMANCE => "performance warning",
+        _ => "warning",
+    };
+    eprintln!(
+        "GL {} in {}: {}",
+        kind,
+        OPERATION.with(Cell::get),
+        String::from_utf8_lossy(message).trim_end()
+    );
+}
+
+/// Names the `gl_safe` call in progress until dropped, then, when errors
+/// are polled, reports any that it raised.
+struct Checked {
+    operation: &'static str,
+    outer: &'static str,
+}
+
+/// Starts checking the GL calls making up `operation` for errors, if debug
+/// output is on.
+fn checked(operation: &'static str) -> Option<Checked> {
+    if !DEBUG.load(Ordering::Relaxed) {
+        return None;
+    }
+    let outer = OPERATION.with(|current| current.replace(operation));
+    Some(Checked { operation, outer })
+}
+
+impl Drop for Checked {
+    fn drop(&mut self) {
+        if !CALLBACK.load(Ordering::Relaxed) {
+            loop {
+                let error = unsafe { glGetError() };
+                let name = match error {
+                    GL_NO_ERROR => break,
+                    GL_INVALID_ENUM => "GL_INVALID_ENUM",
+                    GL_INVALID_VALUE => "GL_INVALID_VALUE",
+                    GL_INVALID_OPERATION => "GL_INVALID_OPERATION",
+                    GL_INVALID_FRAMEBUFFER_OPERATION => "GL_INVALID_FRAMEBUFFER_OPERATION",
+                    GL_OUT_OF_MEMORY => "GL_OUT_OF_MEMORY",
+                    _ => "unknown GL error",
+                };
+                eprintln!("GL error in {}: {}", self.operation, name);
+            }
+        }
+        OPERATION.with(|current| current.set(self.outer));
+    }
+}
+
+pub struct VertexArray<'gl> {
+    id: c_uint,
+    _context: Owner<'gl>,
+}
+impl<'gl> VertexArray<'gl> {
+    pub fn new(_gl: &'gl Context<'_>) -> Option<Self> {
         let mut vao = 0;
+        let _checked = checked("VertexArray::new");
         unsafe { glGenVertexArrays(1, &mut vao) };
         if vao != 0 {
-            Some(Self(vao))
+            Some(Self {
+                id: vao,
+                _context: PhantomData,
+            })
         } else {
             None
         }
     }
 
     pub fn bind(&self) {
-        glBindVertexArray(self.0)
+        let _checked = checked("VertexArray::bind");
+        glBindVertexArray(self.id)
     }
 
     pub fn clear_binding() {
+        let _checked = checked("VertexArray::clear_binding");
         glBindVertexArray(0)
     }
+
+    /// Feeds the shader's attributes from `buffer`, read as an array of `V`.
+    pub fn set_layout<V: VertexLayout>(&self, buffer: &Buffer) {
+        let _checked = checked("VertexArray::set_layout");
+        let floats: i32 = V::ATTRIBUTES.iter().sum();
+        assert_eq!(
+            floats as usize * size_of::<f32>(),
+            size_of::<V>(),
+            "Vertex attributes don't cover the whole vertex"
+        );
+        self.bind();
+        buffer.bind(BufferType::Array);
+        let stride = size_of::<V>().try_into().unwrap();
+        let mut offset = 0;
+        for (index, &components) in V::ATTRIBUTES.iter().enumerate() {
+            unsafe {
+                glVertexAttribPointer(
+                    index as u32,
+                    components,
+                    GL_FLOAT,
+                    0,
+                    stride,
+                    offset as *const c_void,
+                );
+                glEnableVertexAttribArray(index as u32);
+            }
+            offset += components as usize * size_of::<f32>();
+        }
+    }
+}
+
+/// A vertex made of `f32` attributes, which shaders read at locations 0, 1
+/// and so on in the order given.
+pub trait VertexLayout: bytemuck::Pod {
+    /// How many floats each attribute has.
+    const ATTRIBUTES: &'static [i32];
+}
+
+impl Drop for VertexArray<'_> {
+    fn drop(&mut self) {
+        let _checked = checked("VertexArray::drop");
+        unsafe { glDeleteVertexArrays(1, &self.id) };
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -41,30 +384,47 @@ pub enum BufferType {
     ElementArray = GL_ELEMENT_ARRAY_BUFFER.0 as isize,
 }
 
-pub struct Buffer(pub c_uint);
-impl Buffer {
-    pub fn new() -> Option<Self> {
+pub struct Buffer<'gl> {
+    id: c_uint,
+    _context: Owner<'gl>,
+}
+impl<'gl> Buffer<'gl> {
+    pub fn new(_gl: &'gl Context<'_>) -> Option<Self> {
         let mut vbo = 0;
+        let _checked = checked("Buffer::new");
         unsafe {
             glGenBuffers(1, &mut vbo);
         }
         if vbo != 0 {
-            Some(Self(vbo))
+            Some(Self {
+                id: vbo,
+                _context: PhantomData,
+            })
         } else {
             None
         }
     }
 
     pub fn bind(&self, ty: BufferType) {
-        unsafe { glBindBuffer(GLenum(ty as u32), self.0) }
+        let _checked = checked("Buffer::bind");
+        unsafe { glBindBuffer(GLenum(ty as u32), self.id) }
     }
 
     pub fn clear_binding(ty: BufferType) {
+        let _checked = checked("Buffer::clear_binding");
         unsafe { glBindBuffer(GLenum(ty as u32), 0) }
     }
 }
 
+impl Drop for Buffer<'_> {
+    fn drop(&mut self) {
+        let _checked = checked("Buffer::drop");
+        unsafe { glDeleteBuffers(1, &self.id) };
+    }
+}
+
 pub fn buffer_data(ty: BufferType, data: &[u8], usage: GLenum) {
+    let _checked = checked("buffer_data");
     unsafe {
         glBufferData(
             GLenum(ty as u32),
@@ -80,18 +440,28 @@ pub enum ShaderType {
     Fragment = GL_FRAGMENT_SHADER.0 as isize,
 }
 
-pub struct Shader(pub c_uint);
-impl Shader {
+pub struct Shader<'gl> {
+    id: c_uint,
+    /// Names of the uniforms the source declares.
+    uniforms: RefCell<Vec<String>>,
+    _context: Owner<'gl>,
+}
+impl<'gl> Shader<'gl> {
     /// Makes a new shader.
     ///
     /// Prefer the [`Shader::from_source`](Shader::from_source) method.
     ///
     /// Possibly skip the direct creation of the shader object and use
     /// [`ShaderProgram::from_vert_frag`](ShaderProgram::from_vert_frag).
-    pub fn new(ty: ShaderType) -> Option<Self> {
+    pub fn new(_gl: &'gl Context<'_>, ty: ShaderType) -> Option<Self> {
+        let _checked = checked("Shader::new");
         let shader = glCreateShader(GLenum(ty as u32));
         if shader != 0 {
-            Some(Self(shader))
+            Some(Self {
+                id: shader,
+                uniforms: RefCell::default(),
+                _context: PhantomData,
+            })
         } else {
             None
         }
@@ -101,9 +471,11 @@ impl Shader {
     ///
     /// Replaces any previously assigned source.
     pub fn set_source(&self, src: &str) {
+        let _checked = checked("Shader::set_source");
+        *self.uniforms.borrow_mut() = declared_uniforms(src);
         unsafe {
             glShaderSource(
-                self.0,
+                self.id,
                 1,
                 &(src.as_bytes().as_ptr().cast()),
                 &(src.len().try_into().unwrap()),
@@ -113,13 +485,15 @@ impl Shader {
 
     /// Compiles the shader based on the current source.
     pub fn compile(&self) {
-        glCompileShader(self.0);
+        let _checked = checked("Shader::compile");
+        glCompileShader(self.id);
     }
 
     /// Checks if the last compile was successful or not.
     pub fn compile_success(&self) -> bool {
+        let _checked = checked("Shader::compile_success");
         let mut compiled = 0;
-        unsafe { glGetShaderiv(self.0, GL_COMPILE_STATUS, &mut compiled) };
+        unsafe { glGetShaderiv(self.id, GL_COMPILE_STATUS, &mut compiled) };
         compiled == GL_TRUE.0 as i32
     }
 
@@ -127,13 +501,14 @@ impl Shader {
     ///
     /// Usually you use this to get the compilation log when a compile failed.
     pub fn info_log(&self) -> String {
+        let _checked = checked("Shader::info_log");
         let mut needed_len = 0;
-        unsafe { glGetShaderiv(self.0, GL_INFO_LOG_LENGTH, &mut needed_len) };
+        unsafe { glGetShaderiv(self.id, GL_INFO_LOG_LENGTH, &mut needed_len) };
         let mut v: Vec<u8> = Vec::with_capacity(needed_len.try_into().unwrap());
         let mut len_written = 0_i32;
         unsafe {
             glGetShaderInfoLog(
-                self.0,
+                self.id,
                 v.capacity().try_into().unwrap(),
                 &mut len_written,
                 v.as_mut_ptr().cast(),
@@ -143,46 +518,145 @@ impl Shader {
         String::from_utf8_lossy(&v).into_owned()
     }
 
-    /// Marks a shader for deletion.
-    ///
-    /// Note: This _does not_ immediately delete the shader. It only marks it for
-    /// deletion. If the shader has been previously attached to a program then the
-    /// shader will stay allocated until it's unattached from that program.
-    pub fn delete(self) {
-        glDeleteShader(self.0);
-    }
-
     /// Takes a shader type and source string and produces either the compiled
     /// shader or an error message.
     ///
     /// Prefer [`ShaderProgram::from_vert_frag`](ShaderProgram::from_vert_frag),
     /// it makes a complete program from the vertex and fragment sources all at
     /// once.
-    pub fn from_source(ty: ShaderType, source: &str) -> Result<Self, String> {
-        let id = Self::new(ty).ok_or_else(|| "Couldn't allocate new shader".to_string())?;
+    pub fn from_source(gl: &'gl Context<'_>, ty: ShaderType, source: &str) -> Result<Self, String> {
+        let id = Self::new(gl, ty).ok_or_else(|| "Couldn't allocate new shader".to_string())?;
         id.set_source(source);
         id.compile();
         if id.compile_success() {
             Ok(id)
         } else {
-            let out = id.info_log();
-            id.delete();
-            Err(out)
+            Err(id.info_log())
         }
     }
 }
 
-pub struct ShaderProgram(pub c_uint);
-impl ShaderProgram {
+/// Marks the shader for deletion. GL keeps it until it's detached from
+/// every program it was attached to.
+impl Drop for Shader<'_> {
+    fn drop(&mut self) {
+        let _checked = checked("Shader::drop");
+        glDeleteShader(self.id);
+    }
+}
+
+/// Gets the names of the uniforms `source` declares, e.g. `fov` from
+/// `uniform float fov;`, `origins` from `uniform ivec2 origins[4];` and both
+/// `a` and `b` from `layout(location = 2) uniform highp float a, b;`.
+/// Members of uniform blocks aren't included.
+fn declared_uniforms(source: &str) -> Vec<String> {
+    let code = strip_comments(source)
+        .lines()
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut names = Vec::new();
+    for statement in code.split(';') {
+        // Whatever ended before it, such as a function body.
+        let statement = statement.rsplit('}').next().unwrap_or_default().trim();
+        let statement = match statement.strip_prefix("layout") {
+            Some(layout) => layout.split_once(')').map_or("", |(_, rest)| rest),
+            None => statement,
+        };
+        let Some(declaration) = statement.trim_start().strip_prefix("uniform") else {
+            continue;
+        };
+        if !declaration.starts_with(char::is_whitespace) || declaration.contains('{') {
+            continue;
+        }
+        let mut words = declaration
+            .split_whitespace()
+            .skip_while(|word| matches!(*word, "highp" | "mediump" | "lowp"));
+        // The type, then the declarators after it.
+        if words.next().is_none() {
+            continue;
+        }
+        let declarators = words.collect::<Vec<_>>().join(" ");
+        for declarator in split_top_level(&declarators) {
+            let name: String = declarator
+                .trim()
+                .chars()
+                .take_while(|c| c.is_alphanumeric() || *c == '_')
+                .collect();
+            if !name.is_empty() {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+/// Blanks out `//` and `/* */` comments, keeping line breaks.
+fn strip_comments(source: &str) -> String {
+    let mut code = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find("//").into_iter().chain(rest.find("/*")).min() {
+        code.push_str(&rest[..start]);
+        let comment = &rest[start..];
+        let end = if comment.starts_with("//") {
+            comment.find('\n').unwrap_or(comment.len())
+        } else {
+            comment.find("*/").map_or(comment.len(), |end| end + 2)
+        };
+        code.extend(comment[..end].chars().filter(|&c| c == '\n'));
+        code.push(' ');
+        rest = &comment[end..];
+    }
+    code.push_str(rest);
+    code
+}
+
+/// Splits `text` at the commas that aren't inside brackets, such as between
+/// `a = vec2(0, 1), b[2]`.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+pub struct ShaderProgram<'gl> {
+    id: c_uint,
+    /// Names of the uniforms the attached shaders declare.
+    declared: RefCell<HashSet<String>>,
+    /// Uniform locations looked up so far, `None` for uniforms that are
+    /// declared but that the compiler optimised away as unused.
+    locations: RefCell<HashMap<String, Option<i32>>>,
+    _context: Owner<'gl>,
+}
+impl<'gl> ShaderProgram<'gl> {
     /// Allocates a new program object.
     ///
     /// Prefer [`ShaderProgram::from_vert_frag`](ShaderProgram::from_vert_frag),
     /// it makes a complete program from the vertex and fragment sources all at
     /// once.
-    pub fn new() -> Option<Self> {
+    pub fn new(_gl: &'gl Context<'_>) -> Option<Self> {
+        let _checked = checked("ShaderProgram::new");
         let prog = glCreateProgram();
         if prog != 0 {
-            Some(Self(prog))
+            Some(Self {
+                id: prog,
+                declared: RefCell::default(),
+                locations: RefCell::default(),
+                _context: PhantomData,
+            })
         } else {
             None
         }
@@ -190,18 +664,24 @@ impl ShaderProgram {
 
     /// Attaches a shader object to this program object.
     pub fn attach_shader(&self, shader: &Shader) {
-        glAttachShader(self.0, shader.0);
+        let _checked = checked("ShaderProgram::attach_shader");
+        glAttachShader(self.id, shader.id);
+        self.declared
+            .borrow_mut()
+            .extend(shader.uniforms.borrow().iter().cloned());
     }
 
     /// Links the various attached, compiled shader objects into a usable program.
     pub fn link_program(&self) {
-        glLinkProgram(self.0);
+        let _checked = checked("ShaderProgram::link_program");
+        glLinkProgram(self.id);
     }
 
     /// Checks if the last linking operation was successful.
     pub fn link_success(&self) -> bool {
+        let _checked = checked("ShaderProgram::link_success");
         let mut success = 0;
-        unsafe { glGetProgramiv(self.0, GL_LINK_STATUS, &mut success) };
+        unsafe { glGetProgramiv(self.id, GL_LINK_STATUS, &mut success) };
         success == GL_TRUE.0 as i32
     }
 
@@ -209,13 +689,14 @@ impl ShaderProgram {
     ///
     /// This is usually used to check the message when a program failed to link.
     pub fn info_log(&self) -> String {
+        let _checked = checked("ShaderProgram::info_log");
         let mut needed_len = 0;
-        unsafe { glGetProgramiv(self.0, GL_INFO_LOG_LENGTH, &mut needed_len) };
+        unsafe { glGetProgramiv(self.id, GL_INFO_LOG_LENGTH, &mut needed_len) };
         let mut v: Vec<u8> = Vec::with_capacity(needed_len.try_into().unwrap());
         let mut len_written = 0_i32;
         unsafe {
             glGetProgramInfoLog(
-                self.0,
+                self.id,
                 v.capacity().try_into().unwrap(),
                 &mut len_written,
                 v.as_mut_ptr().cast(),
@@ -225,18 +706,13 @@ impl ShaderProgram {
         String::from_utf8_lossy(&v).into_owned()
     }
 
-    /// Sets the program as the program to use when drawing.
-    pub fn use_program(&self) {
-        glUseProgram(self.0);
-    }
-
-    /// Marks the program for deletion.
-    ///
-    /// Note: This _does not_ immediately delete the program. If the program is
-    /// currently in use it won't be deleted until it's not the active program.
-    /// When a program is finally deleted and attached shaders are unattached.
-    pub fn delete(self) {
-        glDeleteProgram(self.0);
+    /// Sets the program as the program to use when drawing, giving access to
+    /// its uniforms for as long as it stays in use.
+    pub fn use_program(&self) -> InUse<'_> {
+        let _checked = checked("ShaderProgram::use_program");
+        glUseProgram(self.id);
+        PROGRAM_IN_USE.with(|in_use| in_use.set(self.id));
+        InUse { program: self }
     }
 
     /// Takes a vertex shader source string and a fragment shader source string
@@ -244,25 +720,142 @@ impl ShaderProgram {
     ///
     /// This is the preferred way to create a simple shader program in the common
     /// case. It's just less error prone than doing all the steps yourself.
-    pub fn from_vert_frag(vert: &str, frag: &str) -> Result<Self, String> {
-        let p = Self::new().ok_or_else(|| "Couldn't allocate a program".to_string())?;
-        let v = Shader::from_source(ShaderType::Vertex, vert)
+    pub fn from_vert_frag(gl: &'gl Context<'_>, vert: &str, frag: &str) -> Result<Self, String> {
+        let p = Self::new(gl).ok_or_else(|| "Couldn't allocate a program".to_string())?;
+        let v = Shader::from_source(gl, ShaderType::Vertex, vert)
             .map_err(|e| format!("Vertex Compile Error: {}", e))?;
-        let f = Shader::from_source(ShaderType::Fragment, frag)
+        let f = Shader::from_source(gl, ShaderType::Fragment, frag)
             .map_err(|e| format!("Fragment Compile Error: {}", e))?;
         p.attach_shader(&v);
         p.attach_shader(&f);
         p.link_program();
-        v.delete();
-        f.delete();
         if p.link_success() {
             Ok(p)
         } else {
-            let out = format!("Program Link Error: {}", p.info_log());
-            p.delete();
-            Err(out)
+            Err(format!("Program Link Error: {}", p.info_log()))
         }
     }
+
+    /// Looks up a uniform's location, or `None` for one the compiler
+    /// optimised away as unused. Names the shaders don't declare are an
+    /// error. Locations are looked up once and cached.
+    pub fn uniform_location(&self, name: &str) -> Result<Option<i32>, String> {
+        if let Some(location) = self.locations.borrow().get(name) {
+            return Ok(*location);
+        }
+        if !self.declared.borrow().contains(name) {
+            return Err(format!(
+                "No uniform `{}` is declared in the shader program",
+                name
+            ));
+        }
+        let c_name = CString::new(name).map_err(|e| format!("Uniform {:?}: {}", name, e))?;
+        let location = unsafe { glGetUniformLocation(self.id, c_name.as_ptr().cast()) };
+        let location = (location >= 0).then_some(location);
+        self.locations
+            .borrow_mut()
+            .insert(name.to_string(), location);
+        Ok(location)
+    }
+}
+
+/// A program that [`ShaderProgram::use_program`] made the one in use, so its
+/// uniforms can be set.
+pub struct InUse<'a> {
+    program: &'a ShaderProgram<'a>,
+}
+
+impl InUse<'_> {
+    /// Sets one of the program's uniforms. Uniforms the compiler optimised
+    /// away are skipped, as GL itself would. Names the shaders don't declare
+    /// are an error, as is another program having been put in use since.
+    pub fn set_uniform<T: Uniform>(&self, name: &str, value: T) -> Result<(), String> {
+        let _checked = checked("InUse::set_uniform");
+        if PROGRAM_IN_USE.with(Cell::get) != self.program.id {
+            return Err(format!(
+                "Setting uniform `{}` after another program was put in use",
+                name
+            ));
+        }
+        if let Some(location) = self.program.uniform_location(name)? {
+            value.set(location);
+        }
+        Ok(())
+    }
+}
+
+/// Marks the program for deletion. GL keeps it until it's no longer the
+/// program in use.
+impl Drop for ShaderProgram<'_> {
+    fn drop(&mut self) {
+        let _checked = checked("ShaderProgram::drop");
+        glDeleteProgram(self.id);
+    }
+}
+
+/// A value that can be stored in a GLSL uniform of the matching type.
+pub trait Uniform {
+    /// Stores the value at `location` in the program in use.
+    fn set(self, location: i32);
+}
+
+/// The texture unit a `sampler2D` uniform reads from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sampler(pub u32);
+
+impl Uniform for f32 {
+    fn set(self, location: i32) {
+        unsafe { glUniform1f(location, self) }
+    }
+}
+
+impl Uniform for i32 {
+    fn set(self, location: i32) {
+        unsafe { glUniform1i(location, self) }
+    }
+}
+
+impl Uniform for bool {
+    fn set(self, location: i32) {
+        unsafe { glUniform1i(location, self.into()) }
+    }
+}
+
+impl Uniform for Vec2 {
+    fn set(self, location: i32) {
+        unsafe { glUniform2f(location, self.x, self.y) }
+    }
+}
+
+impl Uniform for Vec3 {
+    fn set(self, location: i32) {
+        unsafe { glUniform3f(location, self.x, self.y, self.z) }
+    }
+}
+
+impl Uniform for Vec4 {
+    fn set(self, location: i32) {
+        unsafe { glUniform4f(location, self.x, self.y, self.z, self.w) }
+    }
+}
+
+impl Uniform for Mat3 {
+    fn set(self, location: i32) {
+        unsafe { glUniformMatrix3fv(location, 1, 0, self.as_array().as_ptr()) }
+    }
+}
+
+impl Uniform for Sampler {
+    fn set(self, location: i32) {
+        unsafe { glUniform1i(location, self.0 as i32) }
+    }
+}
+
+/// An `ivec2` array.
+impl<const N: usize> Uniform for [[i32; 2]; N] {
+    fn set(self, location: i32) {
+        unsafe { glUniform2iv(location, N as i32, self.as_ptr().cast()) }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -273,52 +866,357 @@ pub enum PolygonMode {
 }
 
 pub fn polygon_mode(mode: PolygonMode) {
+    let _checked = checked("polygon_mode");
     unsafe { glPolygonMode(GL_FRONT_AND_BACK, GLenum(mode as u32)) }
 }
 
-pub struct Texture(pub c_uint);
-impl Texture {
-    pub unsafe fn new() -> Self {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Primitive {
+    Triangles = GL_TRIANGLES.0 as isize,
+    TriangleFan = GL_TRIANGLE_FAN.0 as isize,
+}
+
+pub struct Texture<'gl> {
+    id: c_uint,
+    _context: Owner<'gl>,
+}
+impl<'gl> Texture<'gl> {
+    pub fn new(_gl: &'gl Context<'_>) -> Option<Self> {
         let mut id: c_uint = 0;
-        glGenTextures(1, &mut id);
-        Self(id)
+        let _checked = checked("Texture::new");
+        unsafe { glGenTextures(1, &mut id) };
+        if id != 0 {
+            Some(Self {
+                id,
+                _context: PhantomData,
+            })
+        } else {
+            None
+        }
     }
 
-    pub unsafe fn bind(&self) {
-        glBindTexture(GL_TEXTURE_2D, self.0);
+    pub fn bind(&self) {
+        let _checked = checked("Texture::bind");
+        unsafe { glBindTexture(GL_TEXTURE_2D, self.id) };
     }
 
-    pub unsafe fn load(&self, path: &Path) -> Result<(), ImageError> {
+    /// Loads an image file, returning its width and height.
+    pub fn load(&self, path: &Path) -> Result<(u32, u32), ImageError> {
+        let img = image::open(path)?.into_rgba8();
+        let _checked = checked("Texture::load");
         self.bind();
+        unsafe {
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT.0 as i32);
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT.0 as i32);
+            glTexParameteri(
+                GL_TEXTURE_2D,
+                GL_TEXTURE_MIN_FILTER,
+                GL_LINEAR_MIPMAP_LINEAR.0 as i32,
+            );
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR.0 as i32);
 
-        glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_REPEAT.0 as i32);
-        glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_REPEAT.0 as i32);
-        glTexParameteri(
-            GL_TEXTURE_2D,
-            GL_TEXTURE_MIN_FILTER,
-            GL_LINEAR_MIPMAP_LINEAR.0 as i32,
-        );
-        glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR.0 as i32);
+            glTexImage2D(
+                GL_TEXTURE_2D,
+                0,
+                GL_RGBA.0 as i32,
+                img.width() as i32,
+                img.height() as i32,
+                0,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                img.as_bytes().as_ptr() as *const _,
+            );
+            glGenerateMipmap(GL_TEXTURE_2D);
+        }
+        Ok((img.width(), img.height()))
+    }
 
-        let img = image::open(path)?.into_rgba8();
-        glTexImage2D(
-            GL_TEXTURE_2D,
-            0,
-            GL_RGBA.0 as i32,
-            img.width() as i32,
-            img.height() as i32,
-            0,
-            GL_RGBA,
-            GL_UNSIGNED_BYTE,
-            img.as_bytes().as_ptr() as *const _,
-        );
-        glGenerateMipmap(GL_TEXTURE_2D);
-        Ok(())
+    /// Makes room for `width` by `height` pixels of `format` without filling
+    /// them in, for rendering into through a [`Framebuffer`].
+    // Kept for multi-pass effects and supersampling; the viewer itself only
+    // renders into renderbuffers so far.
+    #[allow(dead_code)]
+    pub fn allocate(&self, width: u32, height: u32, format: ColorFormat) {
+        let _checked = checked("Texture::allocate");
+        self.bind();
+        unsafe {
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE.0 as i32);
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE.0 as i32);
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_LINEAR.0 as i32);
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_LINEAR.0 as i32);
+
+            glTexImage2D(
+                GL_TEXTURE_2D,
+                0,
+                format.internal_format().0 as i32,
+                width as i32,
+                height as i32,
+                0,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                std::ptr::null(),
+            );
+        }
+    }
+
+    /// Uploads raw RGBA pixels without smoothing or wrapping, for pixel art
+    /// such as the HUD font.
+    pub fn load_rgba(&self, width: u32, height: u32, pixels: &[u8]) {
+        let _checked = checked("Texture::load_rgba");
+        assert_eq!(pixels.len(), width as usize * height as usize * 4);
+        self.bind();
+        unsafe {
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, GL_CLAMP_TO_EDGE.0 as i32);
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, GL_CLAMP_TO_EDGE.0 as i32);
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MIN_FILTER, GL_NEAREST.0 as i32);
+            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_MAG_FILTER, GL_NEAREST.0 as i32);
+
+            glTexImage2D(
+                GL_TEXTURE_2D,
+                0,
+                GL_RGBA.0 as i32,
+                width as i32,
+                height as i32,
+                0,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                pixels.as_ptr() as *const _,
+            );
+        }
+    }
+}
+
+impl Drop for Texture<'_> {
+    fn drop(&mut self) {
+        let _checked = checked("Texture::drop");
+        unsafe { glDeleteTextures(1, &self.id) };
+    }
+}
+
+/// How a render target stores colour.
+// Exports only need RGBA8; the others are for passes drawn into textures.
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorFormat {
+    /// 8 bits per channel.
+    Rgba8,
+    /// 8 bits per channel, gamma encoded and blended in linear light.
+    Srgb8Alpha8,
+    /// Half floats, for passes whose results go outside 0 to 1.
+    Rgba16F,
+}
+
+impl ColorFormat {
+    fn internal_format(self) -> GLenum {
+        match self {
+            ColorFormat::Rgba8 => GL_RGBA8,
+            ColorFormat::Srgb8Alpha8 => GL_SRGB8_ALPHA8,
+            ColorFormat::Rgba16F => GL_RGBA16F,
+        }
+    }
+}
+
+/// Image storage a [`Framebuffer`] can draw into but shaders can't sample,
+/// for results that only get read back.
+pub struct Renderbuffer<'gl> {
+    id: c_uint,
+    _context: Owner<'gl>,
+}
+impl<'gl> Renderbuffer<'gl> {
+    /// Makes room for `width` by `height` pixels of `format`.
+    pub fn new(
+        _gl: &'gl Context<'_>,
+        width: u32,
+        height: u32,
+        format: ColorFormat,
+    ) -> Option<Self> {
+        let _checked = checked("Renderbuffer::new");
+        let mut rbo = 0;
+        unsafe { glGenRenderbuffers(1, &mut rbo) };
+        if rbo == 0 {
+            return None;
+        }
+        unsafe {
+            glBindRenderbuffer(GL_RENDERBUFFER, rbo);
+            glRenderbufferStorage(
+                GL_RENDERBUFFER,
+                format.internal_format(),
+                width as i32,
+                height as i32,
+            );
+            glBindRenderbuffer(GL_RENDERBUFFER, 0);
+        }
+        Some(Self {
+            id: rbo,
+            _context: PhantomData,
+        })
     }
 }
 
-impl Drop for Texture {
+impl Drop for Renderbuffer<'_> {
     fn drop(&mut self) {
-        unsafe { glDeleteTextures(1, [self.0].as_ptr()) };
+        let _checked = checked("Renderbuffer::drop");
+        unsafe { glDeleteRenderbuffers(1, &self.id) };
+    }
+}
+
+/// An offscreen render target, drawing into the textures and renderbuffers
+/// attached to it.
+pub struct Framebuffer<'gl> {
+    id: c_uint,
+    /// Bit `i` is set once colour attachment `i` has something attached.
+    attached: Cell<u32>,
+    _context: Owner<'gl>,
+}
+impl<'gl> Framebuffer<'gl> {
+    pub fn new(_gl: &'gl Context<'_>) -> Option<Self> {
+        let mut fbo = 0;
+        let _checked = checked("Framebuffer::new");
+        unsafe { glGenFramebuffers(1, &mut fbo) };
+        if fbo != 0 {
+            Some(Self {
+                id: fbo,
+                attached: Cell::new(0),
+                _context: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn bind(&self) {
+        let _checked = checked("Framebuffer::bind");
+        unsafe { glBindFramebuffer(GL_FRAMEBUFFER, self.id) }
+    }
+
+    /// Goes back to drawing on the window.
+    pub fn clear_binding() {
+        let _checked = checked("Framebuffer::clear_binding");
+        unsafe { glBindFramebuffer(GL_FRAMEBUFFER, 0) }
+    }
+
+    /// Draws fragment shader output `index` into `texture` from now on,
+    /// leaving the framebuffer bound.
+    // See `Texture::allocate`.
+    #[allow(dead_code)]
+    pub fn attach_texture(&self, index: u32, texture: &Texture) {
+        let _checked = checked("Framebuffer::attach_texture");
+        self.bind();
+        unsafe {
+            glFramebufferTexture2D(
+                GL_FRAMEBUFFER,
+                color_attachment(index),
+                GL_TEXTURE_2D,
+                texture.id,
+                0,
+            )
+        }
+        self.draw_to(index);
+    }
+
+    /// Draws fragment shader output `index` into `renderbuffer` from now on,
+    /// leaving the framebuffer bound.
+    pub fn attach_renderbuffer(&self, index: u32, renderbuffer: &Renderbuffer) {
+        let _checked = checked("Framebuffer::attach_renderbuffer");
+        self.bind();
+        unsafe {
+            glFramebufferRenderbuffer(
+                GL_FRAMEBUFFER,
+                color_attachment(index),
+                GL_RENDERBUFFER,
+                renderbuffer.id,
+            )
+        }
+        self.draw_to(index);
+    }
+
+    /// Adds colour attachment `index` to the ones drawn into.
+    fn draw_to(&self, index: u32) {
+        let attached = self.attached.get() | 1 << index;
+        self.attached.set(attached);
+        let count = u32::BITS - attached.leading_zeros();
+        let buffers: Vec<GLenum> = (0..count)
+            .map(|i| {
+                if attached & 1 << i != 0 {
+                    color_attachment(i)
+                } else {
+                    GL_NONE
+                }
+            })
+            .collect();
+        unsafe { glDrawBuffers(buffers.len() as i32, buffers.as_ptr()) }
+    }
+
+    /// Reads back from colour attachment `index` in
+    /// [`Context::read_pixels`]. The framebuffer must be bound.
+    pub fn read_from(&self, index: u32) {
+        let _checked = checked("Framebuffer::read_from");
+        unsafe { glReadBuffer(color_attachment(index)) }
+    }
+
+    /// Checks the framebuffer can be drawn to, saying why not if it can't.
+    /// The framebuffer must be bound.
+    pub fn check(&self) -> Result<(), String> {
+        let _checked = checked("Framebuffer::check");
+        let status = unsafe { glCheckFramebufferStatus(GL_FRAMEBUFFER) };
+        let problem = match status {
+            GL_FRAMEBUFFER_COMPLETE => return Ok(()),
+            GL_FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is unusable",
+            GL_FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "nothing is attached",
+            GL_FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER | GL_FRAMEBUFFER_INCOMPLETE_READ_BUFFER => {
+                "a draw or read buffer has nothing attached"
+            }
+            GL_FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "the attachments' sample counts differ",
+            GL_FRAMEBUFFER_UNSUPPORTED => "the driver doesn't support these attachment formats",
+            _ => "unknown framebuffer status",
+        };
+        Err(format!("Framebuffer incomplete: {}", problem))
+    }
+}
+
+fn color_attachment(index: u32) -> GLenum {
+    GLenum(GL_COLOR_ATTACHMENT0.0 + index)
+}
+
+impl Drop for Framebuffer<'_> {
+    fn drop(&mut self) {
+        let _checked = checked("Framebuffer::drop");
+        unsafe { glDeleteFramebuffers(1, &self.id) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_plain_and_array_uniforms() {
+        let source = "#version 330 core\nuniform float fov;\nuniform ivec2 origins[4];\n";
+        assert_eq!(declared_uniforms(source), ["fov", "origins"]);
+    }
+
+    #[test]
+    fn skips_qualifiers_and_splits_declarators() {
+        let source = "layout(location = 2) uniform highp float a, b[2];\n\
+                      uniform vec2 c = vec2(0.0, 1.0), d;";
+        assert_eq!(declared_uniforms(source), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn ignores_comments_blocks_and_lookalikes() {
+        let source = "//uniform bool twin_view;\n\
+                      /* uniform float old;\n uniform float older; */\n\
+                      layout(std140) uniform Lights { vec3 light; };\n\
+                      void main() { float uniformity = 1.0; }\n\
+                      uniform float zoom; //= 0.9280;";
+        assert_eq!(declared_uniforms(source), ["zoom"]);
+    }
+
+    #[test]
+    fn finds_the_built_in_overlay_uniforms() {
+        let names = declared_uniforms(include_str!("shd/overlay.fs"));
+        for name in ["show_grid", "viewport", "font", "cardinal_origins"] {
+            assert!(names.iter().any(|n| n == name), "{} is missing", name);
+        }
     }
 }
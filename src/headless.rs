@@ -0,0 +1,235 @@
+//! An OpenGL context with no window, made through EGL's surfaceless platform,
+//! for rendering where there's no display server. With Mesa that works on
+//! machines without a GPU too, drawing with llvmpipe on the CPU.
+//!
+//! There's no window to draw into, so everything is drawn offscreen into
+//! [`Framebuffer`](crate::gl_safe::Framebuffer)s, as exports are.
+
+use std::{ffi::c_void, ptr};
+
+use crate::gl_safe::Context;
+
+type EGLDisplay = *mut c_void;
+type EGLConfig = *mut c_void;
+type EGLContext = *mut c_void;
+type EGLSurface = *mut c_void;
+type EGLBoolean = u32;
+type EGLenum = u32;
+type EGLint = i32;
+type EGLAttrib = isize;
+
+const EGL_TRUE: EGLBoolean = 1;
+const EGL_NONE: EGLint = 0x3038;
+const EGL_RENDERABLE_TYPE: EGLint = 0x3040;
+const EGL_SURFACE_TYPE: EGLint = 0x3033;
+const EGL_OPENGL_BIT: EGLint = 0x0008;
+const EGL_OPENGL_API: EGLenum = 0x30A2;
+const EGL_CONTEXT_MAJOR_VERSION: EGLint = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: EGLint = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: EGLint = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: EGLint = 0x0001;
+const EGL_CONTEXT_OPENGL_DEBUG: EGLint = 0x31B0;
+const EGL_PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
+const EGL_NO_CONTEXT: EGLContext = ptr::null_mut();
+const EGL_NO_SURFACE: EGLSurface = ptr::null_mut();
+const EGL_DEFAULT_DISPLAY: *mut c_void = ptr::null_mut();
+
+#[link(name = "EGL")]
+extern "C" {
+    fn eglGetError() -> EGLint;
+    fn eglGetPlatformDisplay(
+        platform: EGLenum,
+        native_display: *mut c_void,
+        attrib_list: *const EGLAttrib,
+    ) -> EGLDisplay;
+    fn eglGetDisplay(native_display: *mut c_void) -> EGLDisplay;
+    fn eglInitialize(display: EGLDisplay, major: *mut EGLint, minor: *mut EGLint) -> EGLBoolean;
+    fn eglTerminate(display: EGLDisplay) -> EGLBoolean;
+    fn eglBindAPI(api: EGLenum) -> EGLBoolean;
+    fn eglChooseConfig(
+        display: EGLDisplay,
+        attrib_list: *const EGLint,
+        configs: *mut EGLConfig,
+        config_size: EGLint,
+        num_config: *mut EGLint,
+    ) -> EGLBoolean;
+    fn eglCreateContext(
+        display: EGLDisplay,
+        config: EGLConfig,
+        share_context: EGLContext,
+        attrib_list: *const EGLint,
+    ) -> EGLContext;
+    fn eglDestroyContext(display: EGLDisplay, context: EGLContext) -> EGLBoolean;
+    fn eglMakeCurrent(
+        display: EGLDisplay,
+        draw: EGLSurface,
+        read: EGLSurface,
+        context: EGLContext,
+    ) -> EGLBoolean;
+    fn eglGetProcAddress(name: *const u8) -> *const c_void;
+}
+
+/// Describes the last EGL error for a step that failed.
+fn egl_error(step: &str) -> String {
+    format!("{} failed with EGL error {:#x}", step, unsafe {
+        eglGetError()
+    })
+}
+
+/// A GL 3.3 core context made current on this thread, with no surface.
+pub struct Headless {
+    display: EGLDisplay,
+    context: EGLContext,
+    /// EGL's `(major, minor)` version.
+    pub version: (i32, i32),
+}
+
+impl Headless {
+    /// Makes the context, with GL's debug output on if `debug` is set.
+    pub fn new(debug: bool) -> Result<Self, String> {
+        unsafe {
+            // Mesa's surfaceless platform needs no display server at all. Other
+            // EGLs may only have the default display.
+            let mut display = eglGetPlatformDisplay(
+                EGL_PLATFORM_SURFACELESS_MESA,
+                EGL_DEFAULT_DISPLAY,
+                ptr::null(),
+            );
+            if display.is_null() {
+                display = eglGetDisplay(EGL_DEFAULT_DISPLAY);
+            }
+            if display.is_null() {
+                return Err(egl_error("eglGetDisplay"));
+            }
+            let (mut major, mut minor) = (0, 0);
+            if eglInitialize(display, &mut major, &mut minor) != EGL_TRUE {
+                return Err(egl_error("eglInitialize"));
+            }
+            let fail = |step: &str| {
+                let error = egl_error(step);
+                eglTerminate(display);
+                error
+            };
+            if eglBindAPI(EGL_OPENGL_API) != EGL_TRUE {
+                return Err(fail("eglBindAPI"));
+            }
+
+            // The surface type defaults to windows, which the surfaceless
+            // platform has none of, so ask for configs of any surface type.
+            let config_attributes = [
+                EGL_RENDERABLE_TYPE,
+                EGL_OPENGL_BIT,
+                EGL_SURFACE_TYPE,
+                0,
+                EGL_NONE,
+            ];
+            let mut config = ptr::null_mut();
+            let mut config_count = 0;
+            if eglChooseConfig(
+                display,
+                config_attributes.as_ptr(),
+                &mut config,
+                1,
+                &mut config_count,
+            ) != EGL_TRUE
+                || config_count < 1
+            {
+                return Err(fail("eglChooseConfig"));
+            }
+
+            let mut context_attributes = vec![
+                EGL_CONTEXT_MAJOR_VERSION,
+                3,
+                EGL_CONTEXT_MINOR_VERSION,
+                3,
+                EGL_CONTEXT_OPENGL_PROFILE_MASK,
+                EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+            ];
+            // EGL 1.4 rejects the attribute altogether, so only ask when needed.
+            if debug {
+                context_attributes.extend([EGL_CONTEXT_OPENGL_DEBUG, EGL_TRUE as EGLint]);
+            }
+            context_attributes.push(EGL_NONE);
+            let context =
+                eglCreateContext(display, config, EGL_NO_CONTEXT, context_attributes.as_ptr());
+            if context.is_null() {
+                return Err(fail("eglCreateContext"));
+            }
+            if eglMakeCurrent(display, EGL_NO_SURFACE, EGL_NO_SURFACE, context) != EGL_TRUE {
+                let error = egl_error("eglMakeCurrent");
+                eglDestroyContext(display, context);
+                eglTerminate(display);
+                return Err(error);
+            }
+            Ok(Self {
+                display,
+                context,
+                version: (major, minor),
+            })
+        }
+    }
+
+    /// Loads the context's GL functions.
+    pub fn load(&self, debug: bool) -> Context<'_> {
+        // Safety: the context was made current on this thread in `new` and
+        // stays current until `self` is dropped, which the borrow prevents.
+        unsafe { Context::load_with(self, &|name| eglGetProcAddress(name), debug) }
+    }
+}
+
+impl Drop for Headless {
+    fn drop(&mut self) {
+        unsafe {
+            eglMakeCurrent(self.display, EGL_NO_SURFACE, EGL_NO_SURFACE, EGL_NO_CONTEXT);
+            eglDestroyContext(self.display, self.context);
+            eglTerminate(self.display);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::Path};
+
+    use super::*;
+    use crate::export::{Export, Format};
+    use crate::gl_safe::Texture;
+    use crate::renderer::Renderer;
+    use crate::shaders::Shaders;
+    use crate::{RotImage, Source};
+
+    /// Draws a small fixed view of a bundled picture and checks it against
+    /// `tests/golden/field`. Run with `GOLDEN_UPDATE=1` to record it again.
+    #[test]
+    fn draws_the_golden_view_of_a_picture() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let headless = Headless::new(false).unwrap();
+        let gl = headless.load(false);
+        gl.enable_blending();
+
+        let texture = Texture::new(&gl).unwrap();
+        texture.load(&root.join("pictures/field.jpg")).unwrap();
+        let mut image = RotImage {
+            source: Source::from_view_arg("180").unwrap(),
+            ..Default::default()
+        };
+        image.change_fov(0.0);
+        let renderer = Renderer::new(&gl, &Shaders::embedded(), &image, 1);
+
+        let update = env::var_os("GOLDEN_UPDATE").is_some();
+        let export = Export {
+            target: root.join("tests/golden/field"),
+            format: if update {
+                Format::GoldenUpdate
+            } else {
+                Format::Golden
+            },
+            fps: 1.0,
+            width: 64,
+            height: 48,
+        };
+        let pose = image.pose();
+        let frames = export.render(&gl, 0.0, |_| pose, &mut image, &renderer, &texture);
+        assert_eq!(frames, Ok(1));
+    }
+}
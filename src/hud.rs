@@ -0,0 +1,571 @@
+use bytemuck::{Pod, Zeroable};
+use gl33::gl_enumerations::*;
+use ultraviolet::{Vec2, Vec3};
+
+use crate::annotations::{Annotations, Shape};
+use crate::bindings::{KeyAction, KeyBindings};
+use crate::compare::CompareLayout;
+use crate::config::HudConfig;
+use crate::font::{self, GLYPH_SIZE};
+use crate::gl_safe::{
+    self, Buffer, Context, Primitive, ShaderProgram, Texture, VertexArray, VertexLayout,
+};
+use crate::projection::Projection;
+use crate::shaders::{self, Shaders};
+use crate::stereo::{StereoLayout, StereoOutput};
+use crate::tour::Hotspot;
+use crate::RotImage;
+
+/// Pixel position, texture coordinate and RGBA colour of a HUD corner, as
+/// read by `text.vs`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct HudVertex {
+    pub pos: [f32; 2],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+}
+
+impl HudVertex {
+    pub fn new(pos: [f32; 2], uv: [f32; 2], color: [f32; 4]) -> Self {
+        Self { pos, uv, color }
+    }
+}
+
+// Safety: all `f32`s, so any bytes are a valid vertex and there's no padding.
+unsafe impl Zeroable for HudVertex {}
+unsafe impl Pod for HudVertex {}
+
+impl VertexLayout for HudVertex {
+    const ATTRIBUTES: &'static [i32] = &[2, 2, 4];
+}
+
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const KEY_COLOR: [f32; 4] = [1.0, 0.85, 0.0, 1.0];
+const PANEL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const ANNOTATION_COLOR: [f32; 4] = [0.2, 1.0, 0.4, 1.0];
+const HOTSPOT_COLOR: [f32; 4] = [0.3, 0.8, 1.0, 1.0];
+
+/// Font pixels between the edge of a panel and its text.
+const PADDING: i32 = 4;
+/// Font pixels between the window edge and a panel.
+const MARGIN: i32 = 4;
+/// Font pixels across a point annotation's marker.
+const MARKER_SIZE: i32 = 5;
+/// Straight pieces each region edge is drawn with.
+const EDGE_STEPS: usize = 16;
+
+/// What the HUD reports about the file on screen.
+pub struct ImageInfo<'a> {
+    pub name: &'a str,
+    pub width: u32,
+    pub height: u32,
+    /// Position in the tour, or in the list of images given on the command
+    /// line.
+    pub index: usize,
+    pub count: usize,
+}
+
+/// Text drawn over the view: annotations, tour hotspots, a status panel and
+/// a key help screen.
+pub struct Hud<'gl> {
+    gl: &'gl Context<'gl>,
+    pub visible: bool,
+    pub help: bool,
+    /// Screen pixels per font pixel.
+    scale: i32,
+    /// Smoothed frames per second.
+    fps: f32,
+    font: Texture<'gl>,
+    atlas_size: (u32, u32),
+    vao: VertexArray<'gl>,
+    vbo: Buffer<'gl>,
+    program: ShaderProgram<'gl>,
+    vertices: Vec<HudVertex>,
+}
+
+/// Makes a vertex array and buffer laid out for [`HudVertex`].
+pub fn vertex_array<'gl>(gl: &'gl Context<'_>) -> (VertexArray<'gl>, Buffer<'gl>) {
+    let vao = VertexArray::new(gl).expect("Couldn't make a VAO");
+    let vbo = Buffer::new(gl).expect("Couldn't make a VBO");
+    vao.set_layout::<HudVertex>(&vbo);
+    (vao, vbo)
+}
+
+impl<'gl> Hud<'gl> {
+    /// Builds the font atlas and text program.
+    pub fn new(gl: &'gl Context<'_>, shaders: &Shaders, config: &HudConfig) -> Self {
+        let (atlas_width, atlas_height, pixels) = font::atlas();
+        let font = Texture::new(gl).expect("Couldn't make a texture");
+        font.load_rgba(atlas_width, atlas_height, &pixels);
+
+        let (vao, vbo) = vertex_array(gl);
+        let program =
+            shaders.build_or_embedded(|shaders| shaders.program(gl, "text.vs", &["text.fs"]));
+        Self {
+            gl,
+            visible: config.visible,
+            help: false,
+            scale: config.scale.max(1),
+            fps: 0.0,
+            font,
+            atlas_size: (atlas_width, atlas_height),
+            vao,
+            vbo,
+            program,
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Rebuilds the text program after its sources changed, keeping the old
+    /// one if the new sources don't compile.
+    pub fn reload(&mut self, gl: &'gl Context<'_>, shaders: &Shaders) {
+        shaders::swap_in(
+            &mut self.program,
+            shaders.program(gl, "text.vs", &["text.fs"]),
+        );
+    }
+
+    /// Feeds in the time the last frame took.
+    pub fn tick(&mut self, dt: f32) {
+        if dt <= 0.0 {
+            return;
+        }
+        if self.fps == 0.0 {
+            self.fps = 1.0 / dt;
+        } else {
+            self.fps += (1.0 / dt - self.fps) * 0.05;
+        }
+    }
+
+    /// Draws the annotations, hotspots and whichever panels are turned on over
+    /// a `width` by `height` window. Fails if the text shaders lack a uniform
+    /// the HUD sets.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        image: &RotImage,
+        annotations: &Annotations,
+        hotspots: &[Hotspot],
+        info: &ImageInfo,
+        bindings: &KeyBindings,
+        width: i32,
+        height: i32,
+    ) -> Result<(), String> {
+        self.vertices.clear();
+        self.push_annotations(image, annotations, width, height);
+        self.push_hotspots(image, hotspots, width, height);
+        if self.visible {
+            let lines = self.status_lines(image, info, bindings);
+            let margin = MARGIN * self.scale;
+            self.push_panel(margin, margin, &[lines]);
+        }
+        if self.help {
+            self.push_help(bindings, width, height);
+        }
+        if self.vertices.is_empty() {
+            return Ok(());
+        }
+
+        let screen_size = Vec2::new(width as f32, height as f32);
+        self.program
+            .use_program()
+            .set_uniform("screen_size", screen_size)?;
+        self.vao.bind();
+        self.vbo.bind(gl_safe::BufferType::Array);
+        gl_safe::buffer_data(
+            gl_safe::BufferType::Array,
+            bytemuck::cast_slice(&self.vertices),
+            GL_STREAM_DRAW,
+        );
+        self.gl.viewport([0, 0, width, height]);
+        self.gl.active_texture(0);
+        self.font.bind();
+        self.gl
+            .draw_arrays(Primitive::Triangles, 0, self.vertices.len() as i32);
+        Ok(())
+    }
+
+    fn status_lines(
+        &self,
+        image: &RotImage,
+        info: &ImageInfo,
+        bindings: &KeyBindings,
+    ) -> Vec<(String, [f32; 4])> {
+        let mut lines = Vec::new();
+        let mut file = format!("{}  {}x{}", info.name, info.width, info.height);
+        if info.count > 1 {
+            file += &format!("  [{}/{}]", info.index + 1, info.count);
+        }
+        lines.push(file);
+
+        let mut projection = format!(
+            "{}  fov {:.1}°",
+            image.projection.name(),
+            image.fov.to_degrees()
+        );
+        if image.projection == Projection::Pannini {
+            projection += &format!("  d {:.2}", image.pannini_compression);
+        }
+        if image.overview {
+            projection += "  (overview)";
+        }
+        lines.push(projection);
+
+        let [yaw, pitch, roll] = image.view_angles().map(f32::to_degrees);
+        lines.push(format!(
+            "yaw {:.1}°  pitch {:.1}°  roll {:.1}°",
+            yaw, pitch, roll
+        ));
+        if let Some(heading) = image.overlays.heading(yaw.to_radians()) {
+            lines.push(format!("heading {:.1}°", heading.to_degrees()));
+        }
+
+        let source = &image.source;
+        lines.push(if source.twin_view {
+            format!("twin fisheyes  zoom {:.3}", source.zoom)
+        } else {
+            format!("source {:.0}°", source.source_fov.to_degrees())
+        });
+
+        let mut modes = Vec::new();
+        if image.free_look {
+            modes.push("free look".to_string());
+        }
+        if image.level_horizon {
+            modes.push("level horizon".to_string());
+        }
+        if image.stereo.layout != StereoLayout::Mono || image.stereo.output != StereoOutput::Mono {
+            let layout = match image.stereo.layout {
+                StereoLayout::Mono => "mono",
+                StereoLayout::TopBottom => "top/bottom",
+                StereoLayout::LeftRight => "left/right",
+            };
+            let output = match image.stereo.output {
+                StereoOutput::Mono => "left eye",
+                StereoOutput::SideBySide => "side by side",
+                StereoOutput::Anaglyph => "anaglyph",
+            };
+            modes.push(format!("stereo {} as {}", layout, output));
+        }
+        if let Some(compare) = &image.compare {
+            modes.push(match compare.layout {
+                CompareLayout::SideBySide => "comparing side by side".to_string(),
+                CompareLayout::Wipe => "comparing with wipe".to_string(),
+            });
+        }
+        if !modes.is_empty() {
+            lines.push(modes.join(", "));
+        }
+
+        lines.push(format!("{:.0} fps", self.fps));
+        let mut lines: Vec<_> = lines.into_iter().map(|line| (line, TEXT_COLOR)).collect();
+        if let Some(key) = bindings.keys(KeyAction::ToggleHelp).first() {
+            lines.push((format!("{} for help", key), KEY_COLOR));
+        }
+        lines
+    }
+
+    /// Lays out every bound action in as many columns as the window height
+    /// needs, centred in the window.
+    fn push_help(&mut self, bindings: &KeyBindings, width: i32, height: i32) {
+        let mut entries: Vec<(String, &str)> = KeyAction::ALL
+            .iter()
+            .filter(|&&action| !bindings.keys(action).is_empty())
+            .map(|&action| (bindings.keys(action).join(" / "), action.description()))
+            .collect();
+        entries.push(("Drag".to_string(), "look around, let go to flick"));
+        entries.push(("Click".to_string(), "look there, in the overview"));
+        entries.push(("Click hotspot".to_string(), "go to the linked scene"));
+        entries.push(("1-9".to_string(), "go to bookmark"));
+        entries.push(("Ctrl+1-9".to_string(), "bookmark this view"));
+        entries.push(("Right click".to_string(), "annotate a point"));
+        entries.push((
+            "Shift+right click".to_string(),
+            "add a region corner, Enter to close",
+        ));
+        let key_width = entries
+            .iter()
+            .map(|(keys, _)| keys.len())
+            .max()
+            .unwrap_or(0);
+
+        let line_height = self.line_height();
+        let rows = ((height - 2 * (MARGIN + PADDING) * self.scale) / line_height).max(1) as usize;
+        let columns: Vec<Vec<(String, [f32; 4])>> = entries
+            .chunks(rows)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .map(|(keys, description)| {
+                        (
+                            format!("{:width$}  {}", keys, description, width = key_width),
+                            TEXT_COLOR,
+                        )
+                    })
+                    .collect()
+            })
+            .collect();
+        let (panel_width, panel_height) = self.panel_size(&columns);
+        self.push_panel(
+            (width - panel_width).max(0) / 2,
+            (height - panel_height).max(0) / 2,
+            &columns,
+        );
+    }
+
+    /// Adds a marker and label for every annotation in view, plus the corners
+    /// placed so far of a region being drawn. Annotations are only drawn when
+    /// one camera fills the window.
+    fn push_annotations(
+        &mut self,
+        image: &RotImage,
+        annotations: &Annotations,
+        width: i32,
+        height: i32,
+    ) {
+        if !image.single_view() {
+            return;
+        }
+        let to_pixels = |ray: Vec3| to_pixels(image, ray, width, height);
+        // Lines between points further apart than this cross a seam in the
+        // projection rather than the view.
+        let max_jump = width.max(height) as f32 / 2.0;
+
+        for (index, annotation) in annotations.items.iter().enumerate() {
+            let editing = annotations
+                .editing
+                .as_ref()
+                .is_some_and(|editing| editing.index == index);
+            let color = if editing { KEY_COLOR } else { ANNOTATION_COLOR };
+            if let Shape::Region { corners } = &annotation.shape {
+                let corners: Vec<Vec3> = corners.iter().map(|c| c.to_vector()).collect();
+                let outline: Vec<_> = great_circle_path(&corners, true)
+                    .into_iter()
+                    .map(to_pixels)
+                    .collect();
+                self.push_polyline(&outline, max_jump, color);
+            }
+            let Some((x, y)) = on_screen(to_pixels(annotation.anchor()), width, height) else {
+                continue;
+            };
+            let mut label = annotation.label.clone();
+            if editing {
+                label.push('_');
+            }
+            if let Shape::Point { .. } = annotation.shape {
+                self.push_marker(x, y, color);
+                self.push_label(x + MARKER_SIZE * self.scale, y, label, color);
+            } else {
+                self.push_label(x, y, label, color);
+            }
+        }
+
+        if !annotations.pending_region.is_empty() {
+            let corners: Vec<Vec3> = annotations
+                .pending_region
+                .iter()
+                .map(|c| c.to_vector())
+                .collect();
+            let outline: Vec<_> = great_circle_path(&corners, false)
+                .into_iter()
+                .map(to_pixels)
+                .collect();
+            self.push_polyline(&outline, max_jump, KEY_COLOR);
+            for &corner in &corners {
+                if let Some([x, y]) = to_pixels(corner) {
+                    self.push_marker(x as i32, y as i32, KEY_COLOR);
+                }
+            }
+        }
+    }
+
+    /// Adds a marker and the name of the scene it leads to for every hotspot
+    /// in view.
+    fn push_hotspots(&mut self, image: &RotImage, hotspots: &[Hotspot], width: i32, height: i32) {
+        if !image.single_view() {
+            return;
+        }
+        for hotspot in hotspots {
+            let point = to_pixels(image, hotspot.at.to_vector(), width, height);
+            if let Some((x, y)) = on_screen(point, width, height) {
+                self.push_marker(x, y, HOTSPOT_COLOR);
+                let label = format!("> {}", hotspot.label());
+                self.push_label(x + MARKER_SIZE * self.scale, y, label, HOTSPOT_COLOR);
+            }
+        }
+    }
+
+    /// Adds a one line panel whose left edge is centred on pixel `x`, `y`.
+    /// Empty labels are left out.
+    fn push_label(&mut self, x: i32, y: i32, label: String, color: [f32; 4]) {
+        if label.is_empty() {
+            return;
+        }
+        let lines = [vec![(label, color)]];
+        let (_, panel_height) = self.panel_size(&lines);
+        self.push_panel(x, y - panel_height / 2, &lines);
+    }
+
+    /// Adds a square marker with a dark border centred on pixel `x`, `y`.
+    fn push_marker(&mut self, x: i32, y: i32, color: [f32; 4]) {
+        let inner = MARKER_SIZE * self.scale;
+        let outer = inner + 2 * self.scale;
+        self.push_quad(
+            [x - outer / 2, y - outer / 2, outer, outer],
+            font::SOLID_CELL,
+            PANEL_COLOR,
+        );
+        self.push_quad(
+            [x - inner / 2, y - inner / 2, inner, inner],
+            font::SOLID_CELL,
+            color,
+        );
+    }
+
+    /// Adds lines joining consecutive pixel points, skipping any piece with a
+    /// missing end or longer than `max_jump`.
+    fn push_polyline(&mut self, points: &[Option<[f32; 2]>], max_jump: f32, color: [f32; 4]) {
+        for pair in points.windows(2) {
+            if let [Some(a), Some(b)] = pair {
+                if (b[0] - a[0]).hypot(b[1] - a[1]) <= max_jump {
+                    self.push_segment(*a, *b, color);
+                }
+            }
+        }
+    }
+
+    /// Adds a line one font pixel wide between two pixel points.
+    fn push_segment(&mut self, a: [f32; 2], b: [f32; 2], color: [f32; 4]) {
+        let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+        let length = dx.hypot(dy);
+        if length < 1e-3 {
+            return;
+        }
+        let half_width = self.scale as f32 / 2.0;
+        let (nx, ny) = (-dy / length * half_width, dx / length * half_width);
+        let [u0, v0, u1, v1] =
+            font::cell_uv(font::SOLID_CELL, self.atlas_size.0, self.atlas_size.1);
+        let (u, v) = ((u0 + u1) / 2.0, (v0 + v1) / 2.0);
+        let corner = |x: f32, y: f32| HudVertex::new([x, y], [u, v], color);
+        let a_left = corner(a[0] + nx, a[1] + ny);
+        let a_right = corner(a[0] - nx, a[1] - ny);
+        let b_left = corner(b[0] + nx, b[1] + ny);
+        let b_right = corner(b[0] - nx, b[1] - ny);
+        self.vertices
+            .extend_from_slice(&[a_left, a_right, b_left, b_left, a_right, b_right]);
+    }
+
+    fn line_height(&self) -> i32 {
+        (GLYPH_SIZE as i32 + 2) * self.scale
+    }
+
+    fn text_width(&self, text: &str) -> i32 {
+        text.chars().count() as i32 * GLYPH_SIZE as i32 * self.scale
+    }
+
+    fn panel_size(&self, columns: &[Vec<(String, [f32; 4])>]) -> (i32, i32) {
+        let padding = PADDING * self.scale;
+        let width: i32 = columns
+            .iter()
+            .map(|column| self.column_width(column) + padding)
+            .sum();
+        let rows = columns.iter().map(Vec::len).max().unwrap_or(0) as i32;
+        (width + padding, rows * self.line_height() + 2 * padding)
+    }
+
+    fn column_width(&self, column: &[(String, [f32; 4])]) -> i32 {
+        column
+            .iter()
+            .map(|(line, _)| self.text_width(line))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Adds a translucent panel at the top left pixel `x`, `y` holding
+    /// columns of coloured lines.
+    fn push_panel(&mut self, x: i32, y: i32, columns: &[Vec<(String, [f32; 4])>]) {
+        let (width, height) = self.panel_size(columns);
+        self.push_quad([x, y, width, height], font::SOLID_CELL, PANEL_COLOR);
+        let padding = PADDING * self.scale;
+        let mut column_x = x + padding;
+        for column in columns {
+            for (row, (line, color)) in column.iter().enumerate() {
+                let line_y = y + padding + row as i32 * self.line_height() + self.scale;
+                self.push_text(column_x, line_y, line, *color);
+            }
+            column_x += self.column_width(column) + padding;
+        }
+    }
+
+    fn push_text(&mut self, x: i32, y: i32, text: &str, color: [f32; 4]) {
+        let size = GLYPH_SIZE as i32 * self.scale;
+        for (i, c) in text.chars().enumerate() {
+            if c != ' ' {
+                self.push_quad([x + i as i32 * size, y, size, size], font::cell(c), color);
+            }
+        }
+    }
+
+    /// Adds two triangles covering an `[x, y, width, height]` pixel rectangle,
+    /// measured from the top left, textured with an atlas cell.
+    fn push_quad(&mut self, rect: [i32; 4], cell: usize, color: [f32; 4]) {
+        let [x, y, width, height] = rect.map(|v| v as f32);
+        let [u0, v0, u1, v1] = font::cell_uv(cell, self.atlas_size.0, self.atlas_size.1);
+        let corner = |px: f32, py: f32, u: f32, v: f32| HudVertex::new([px, py], [u, v], color);
+        let top_left = corner(x, y, u0, v0);
+        let top_right = corner(x + width, y, u1, v0);
+        let bottom_left = corner(x, y + height, u0, v1);
+        let bottom_right = corner(x + width, y + height, u1, v1);
+        self.vertices.extend_from_slice(&[
+            top_left,
+            bottom_left,
+            top_right,
+            top_right,
+            bottom_left,
+            bottom_right,
+        ]);
+    }
+}
+
+/// Gets the window pixel, measured from the top left, showing source space
+/// direction `ray`. Points far off screen are dropped, as they only matter as
+/// the ends of lines.
+fn to_pixels(image: &RotImage, ray: Vec3, width: i32, height: i32) -> Option<[f32; 2]> {
+    let [x, y] = image.screen_point(ray)?;
+    if x.abs() > 4.0 || y.abs() > 4.0 {
+        return None;
+    }
+    Some([
+        (x + 1.0) / 2.0 * width as f32,
+        (1.0 - y) / 2.0 * height as f32,
+    ])
+}
+
+/// Keeps a pixel only if it lies inside the window.
+fn on_screen(point: Option<[f32; 2]>, width: i32, height: i32) -> Option<(i32, i32)> {
+    let [x, y] = point?;
+    if x < 0.0 || y < 0.0 || x > width as f32 || y > height as f32 {
+        return None;
+    }
+    Some((x as i32, y as i32))
+}
+
+/// Gets points along the great circles joining consecutive `corners`, back to
+/// the first one if `closed`.
+fn great_circle_path(corners: &[Vec3], closed: bool) -> Vec<Vec3> {
+    let mut ends: Vec<Vec3> = corners.to_vec();
+    if closed && corners.len() > 2 {
+        ends.push(corners[0]);
+    }
+    let mut path = Vec::new();
+    for pair in ends.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        for step in 0..EDGE_STEPS {
+            let t = step as f32 / EDGE_STEPS as f32;
+            path.push((a * (1.0 - t) + b * t).normalized());
+        }
+    }
+    path.extend(ends.last());
+    path
+}
@@ -1,135 +1,179 @@
 use beryllium::{
-    events::Event,
     init::InitFlags,
-    video::{CreateWinArgs, GlProfile, GlSwapInterval},
+    video::{CreateWinArgs, GlContextFlags, GlProfile, GlSwapInterval, GlWindow},
     Sdl,
 };
 
-use core::ffi::c_void;
-use fermium::keycode::*;
-use gl33::{gl_enumerations::*, global_loader::*};
-use nalgebra_glm::{rotate_x_vec3, rotate_y_vec3, vec3};
+use bytemuck::{Pod, Zeroable};
 use std::env;
-use std::ffi::CString;
-use std::io::Read;
-use std::{convert::TryInto, mem::size_of, path::Path};
-use std::{f32::consts::PI, fs::File};
+use std::f32::consts::PI;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use ultraviolet::{Mat3, Rotor3, Vec3};
 
-use crate::gl_safe::{Buffer, ShaderProgram, VertexArray};
+use crate::annotations::Annotations;
+use crate::auto_rotate::AutoRotate;
+use crate::bookmarks::Bookmarks;
+use crate::camera_path::CameraPath;
+use crate::compare::{CompareLayout, Comparison};
+use crate::config::Config;
+use crate::controller::{Action, CameraController};
+use crate::export::{Export, Format};
+use crate::gl_safe::{Context, Texture, VertexLayout};
+#[cfg(feature = "headless")]
+use crate::headless::Headless;
+use crate::hud::{Hud, ImageInfo};
+use crate::minimap::Minimap;
+use crate::overlays::Overlays;
+use crate::pose::Pose;
+use crate::projection::Projection;
+use crate::renderer::Renderer;
+use crate::shaders::Shaders;
+use crate::slideshow::{Slideshow, Transition};
+use crate::stereo::{Stereo, StereoLayout, StereoOutput};
+use crate::tour::{Scene, Tour};
 
+mod annotations;
+mod auto_rotate;
+mod bindings;
+mod bookmarks;
+mod camera_path;
+mod compare;
+mod config;
+mod controller;
+mod export;
+mod font;
 mod gl_safe;
+#[cfg(feature = "headless")]
+mod headless;
+mod hud;
+mod metadata;
+mod minimap;
+mod overlays;
+mod pose;
+mod projection;
+mod renderer;
+mod shaders;
+mod sidecar;
+mod slideshow;
+mod stereo;
+mod tour;
 
-type Vertex = [f32; 5];
+/// A corner of the full screen quad and the rectilinear view ray through it,
+/// as read by `project.vs`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct Vertex {
+    pos: [f32; 2],
+    ray: [f32; 3],
+}
+
+impl Vertex {
+    fn corner(x: f32, y: f32) -> Self {
+        Self {
+            pos: [x, y],
+            ray: [0.0; 3],
+        }
+    }
+}
 
+// Safety: all `f32`s, so any bytes are a valid vertex and there's no padding.
+unsafe impl Zeroable for Vertex {}
+unsafe impl Pod for Vertex {}
+
+impl VertexLayout for Vertex {
+    const ATTRIBUTES: &'static [i32] = &[2, 3];
+}
+
+#[derive(Clone)]
 struct RotImage {
     viewrays: [Vertex; 4],
     fov: f32,
-    camera_rot: [f32; 2],
+    /// Maps camera space rays (looking down `-z`) into source space.
+    orientation: Rotor3,
+    /// Yaw turns about the camera's own up axis instead of the world's.
+    free_look: bool,
+    /// Continuously removes any roll so the horizon stays level.
+    level_horizon: bool,
+    projection: Projection,
+    /// Shows the whole sphere unwrapped, with the current view outlined.
+    overview: bool,
+    stereo: Stereo,
+    /// Pannini's `d` parameter: 0 is rectilinear, 1 is cylindrical stereographic.
+    pannini_compression: f32,
+    source: Source,
+    /// A second panorama shown beside the first through the same camera.
+    compare: Option<Comparison>,
+    overlays: Overlays,
+}
+
+/// How a source image maps directions to texture coordinates.
+#[derive(Debug, Clone, Copy)]
+struct Source {
     source_fov: f32,
     twin_view: bool,
     zoom: f32,
+    /// Turns the source about the vertical by this many radians of yaw.
+    yaw: f32,
 }
 
-struct CameraController {
-    rot_mutation: [f32; 2],
-    fov_mutation: f32,
-    camera_rot_amount: f32,
-    fov_scale_amount: f32,
-    zoom_mutation: f32,
-    zoom_scale_amount: f32,
-}
-impl Default for CameraController {
-    fn default() -> Self {
-        Self {
-            rot_mutation: [0.0, 0.0],
-            fov_mutation: 0.0,
-            camera_rot_amount: 0.03,
-            fov_scale_amount: 0.05,
-            zoom_mutation: 0.0,
-            zoom_scale_amount: 0.0003,
+impl Source {
+    /// Parses a view type argument: an angle in degrees, or `t`/`twin` for two
+    /// 180 degree fisheyes side by side.
+    fn from_view_arg(view: &str) -> Result<Self, String> {
+        if view == "t" || view == "twin" {
+            Ok(Self {
+                source_fov: PI,
+                twin_view: true,
+                zoom: 1.0,
+                yaw: 0.0,
+            })
+        } else {
+            let degrees = view.parse::<f32>().map_err(|_| {
+                format!(
+                    "invalid view type {:?}; it must be the source's field of view in degrees, or t for twin",
+                    view
+                )
+            })?;
+            Ok(Self {
+                source_fov: degrees * PI / 180.,
+                twin_view: false,
+                zoom: 1.0,
+                yaw: 0.0,
+            })
         }
     }
-}
-
-impl CameraController {
-    fn handle_inputs(&mut self, sdl: &Sdl, image: &mut RotImage) -> (bool, bool) {
-        let rot_amount = self.camera_rot_amount;
-        let fov_scale_amount = self.fov_scale_amount;
-        let zoom_scale_amount = self.zoom_scale_amount;
-        let mut update_camera = false;
-        let mut exit = false;
-        while let Some((event, _timestamp)) = sdl.poll_events() {
-            match event {
-                Event::Quit => {
-                    exit = true;
-                    break;
-                }
-                Event::Key {
-                    pressed,
-                    repeat: 0,
-                    keycode,
-                    ..
-                } => match keycode {
-                    SDLK_LEFT | SDLK_a => {
-                        self.rot_mutation[0] += if pressed { rot_amount } else { -rot_amount };
-                    }
-                    SDLK_RIGHT | SDLK_d => {
-                        self.rot_mutation[0] -= if pressed { rot_amount } else { -rot_amount };
-                    }
-                    SDLK_UP | SDLK_w => {
-                        self.rot_mutation[1] += if pressed { rot_amount } else { -rot_amount };
-                    }
-                    SDLK_DOWN | SDLK_s => {
-                        self.rot_mutation[1] -= if pressed { rot_amount } else { -rot_amount };
-                    }
-                    SDLK_e => {
-                        self.fov_mutation -= if pressed {
-                            fov_scale_amount
-                        } else {
-                            -fov_scale_amount
-                        };
-                    }
-                    SDLK_q => {
-                        self.fov_mutation += if pressed {
-                            fov_scale_amount
-                        } else {
-                            -fov_scale_amount
-                        };
-                    }
-                    SDLK_LEFTBRACKET | SDLK_r => {
-                        self.zoom_mutation -= if pressed {
-                            zoom_scale_amount
-                        } else {
-                            -zoom_scale_amount
-                        };
-                    }
-                    SDLK_RIGHTBRACKET | SDLK_f => {
-                        self.zoom_mutation += if pressed {
-                            zoom_scale_amount
-                        } else {
-                            -zoom_scale_amount
-                        };
-                    }
-                    SDLK_ESCAPE => {
-                        exit = true;
-                        break;
-                    }
-                    _ => {}
-                },
-                _ => {}
+    /// Gets the rotation applied to source space directions before sampling.
+    fn rotation(&self) -> Rotor3 {
+        Rotor3::from_rotation_xz(self.yaw)
+    }
+    fn get_scalar(&self) -> f32 {
+        1.0 / (self.source_fov / 4.0).sin()
+    }
+    /// Gets the texture coordinate a source space direction is read from,
+    /// wrapped into 0 to 1, or `None` if it falls outside the source. Matches
+    /// `sample_source` in `project.fs`.
+    fn direction_uv(&self, ray: Vec3) -> Option<[f32; 2]> {
+        let ray = self.rotation() * ray;
+        let z = if self.twin_view { ray.z.abs() } else { ray.z };
+        let scale = 1.0 / (2.0 * 2f32.sqrt() * (z + 1.0).sqrt());
+        let scaled = [ray.x * scale, ray.y * scale].map(|c| c * self.get_scalar());
+        if !self.twin_view && scaled[0].hypot(scaled[1]) >= 0.5 && self.get_scalar() > 1.0 {
+            return None;
+        }
+        let zoom = if self.twin_view { self.zoom } else { 1.0 };
+        let mut u = 1.0 - (scaled[0] * zoom - 0.5);
+        let v = -(scaled[1] * zoom - 0.5);
+        if self.twin_view {
+            u /= 2.0;
+            if ray.z < 0.0 {
+                u = 1.0 - u;
             }
         }
-        if self.rot_mutation[0] != 0.0
-            || self.rot_mutation[1] != 0.0
-            || self.fov_mutation != 0.0
-            || self.zoom_mutation != 0.0
-        {
-            update_camera = true;
-            image.fov += self.fov_mutation;
-            image.zoom += self.zoom_mutation;
-            image.rotate_viewrays(self.rot_mutation[0], self.rot_mutation[1]);
+        if !u.is_finite() || !v.is_finite() {
+            return None;
         }
-        (update_camera, exit)
+        Some([u.rem_euclid(1.0), v.rem_euclid(1.0)])
     }
 }
 
@@ -137,186 +181,878 @@ impl Default for RotImage {
     fn default() -> Self {
         Self {
             viewrays: [
-                [-1., 1., 0.0, 0.0, 0.0],
-                [1., 1., 0.0, 0.0, 0.0],
-                [1., -1., 0.0, 0.0, 0.0],
-                [-1., -1., 0.0, 0.0, 0.0],
+                Vertex::corner(-1., 1.),
+                Vertex::corner(1., 1.),
+                Vertex::corner(1., -1.),
+                Vertex::corner(-1., -1.),
             ],
             fov: PI / 2.0,
-            camera_rot: [0., 0.],
-            source_fov: 2. * PI,
-            twin_view: false,
-            zoom: 1.0,
+            orientation: Rotor3::from_rotation_xz(-PI / 2.),
+            free_look: false,
+            level_horizon: false,
+            projection: Projection::Rectilinear,
+            overview: false,
+            stereo: Stereo::default(),
+            pannini_compression: 1.0,
+            source: Source {
+                source_fov: 2. * PI,
+                twin_view: false,
+                zoom: 1.0,
+                yaw: 0.0,
+            },
+            compare: None,
+            overlays: Overlays::default(),
         }
     }
 }
 
 impl RotImage {
-    fn rotate_viewrays(&mut self, angle_x: f32, angle_y: f32) {
+    /// Turns the camera by the given yaw, pitch and roll deltas in radians.
+    ///
+    /// Pitch and roll are always about the camera's own axes. Yaw is about
+    /// the world's vertical unless `free_look` is set.
+    fn rotate_viewrays(&mut self, yaw: f32, pitch: f32, roll: f32) {
+        let yaw = Rotor3::from_rotation_xz(-yaw);
+        let local = Rotor3::from_rotation_yz(pitch) * Rotor3::from_rotation_xy(roll);
+        self.orientation = if self.free_look {
+            self.orientation * yaw * local
+        } else {
+            yaw * self.orientation * local
+        };
+        if self.level_horizon {
+            self.level();
+        }
+        self.orientation.normalize();
+
         let distance = self.get_distance();
-        self.camera_rot[0] += angle_x;
-        self.camera_rot[1] += angle_y;
         for v in &mut self.viewrays {
-            let mut vec = vec3(v[0] * 0.5, v[1] * 0.5, distance);
-            self.camera_rot[1] = f32::max(-PI / 2., self.camera_rot[1]);
-            self.camera_rot[1] = f32::min(PI / 2., self.camera_rot[1]);
-            vec = rotate_x_vec3(&vec, self.camera_rot[1]);
-            vec = rotate_y_vec3(&vec, self.camera_rot[0] + PI / 2.);
-            v[2] = vec[0];
-            v[3] = vec[1];
-            v[4] = vec[2];
+            let [x, y] = v.pos;
+            let ray = self.orientation * Vec3::new(x * 0.5, y * 0.5, distance);
+            v.ray = ray.into();
         }
     }
-    fn get_scalar(&self) -> f32 {
-        1.0 / (self.source_fov / 4.0).sin()
+    /// Rolls the camera about its view direction until its right axis is
+    /// horizontal. Does nothing when looking straight up or down.
+    fn level(&mut self) {
+        let forward = self.orientation * -Vec3::unit_z();
+        let right = self.orientation * Vec3::unit_x();
+        let mut level_right = forward.cross(Vec3::unit_y());
+        if level_right.mag_sq() < 1e-6 {
+            return;
+        }
+        level_right.normalize();
+        // Past a pole the view is upside down; keep it that way.
+        if level_right.dot(right) < 0.0 {
+            level_right = -level_right;
+        }
+        self.orientation = Rotor3::from_rotation_between(right, level_right) * self.orientation;
+    }
+    /// Points the camera at a longitude and latitude in the overview's
+    /// coordinates, dropping any roll.
+    fn look_at(&mut self, lon: f32, lat: f32) {
+        self.orientation = Rotor3::from_rotation_xz(lon - PI / 2.) * Rotor3::from_rotation_yz(lat);
+        self.rotate_viewrays(0.0, 0.0, 0.0);
+    }
+    /// Turns the camera about the world's vertical, adding `angle` radians to
+    /// its yaw.
+    fn turn(&mut self, angle: f32) {
+        self.orientation = Rotor3::from_rotation_xz(angle) * self.orientation;
+        self.rotate_viewrays(0.0, 0.0, 0.0);
+    }
+    /// Switches to whichever projection settings a scene gives and, if
+    /// `initial_view` is set, to the view it starts from.
+    fn apply_scene(&mut self, scene: &Scene, initial_view: bool) {
+        if let Some(projection) = scene.projection {
+            self.set_projection(projection);
+        }
+        if let Some(fov) = scene.fov {
+            self.fov = fov.to_radians();
+            self.change_fov(0.0);
+        }
+        if initial_view && (scene.yaw.is_some() || scene.pitch.is_some()) {
+            let (yaw, pitch) = (scene.yaw.unwrap_or(0.0), scene.pitch.unwrap_or(0.0));
+            self.look_at(yaw.to_radians(), pitch.to_radians());
+        }
+        self.rotate_viewrays(0.0, 0.0, 0.0);
+    }
+    /// Gets the camera's `[yaw, pitch, roll]` in radians. Yaw and pitch are
+    /// the longitude and latitude it looks at, as in the overview.
+    fn view_angles(&self) -> [f32; 3] {
+        self.pose().angles()
+    }
+    fn pose(&self) -> Pose {
+        Pose {
+            orientation: self.orientation,
+            fov: self.fov,
+            zoom: self.source.zoom,
+        }
+    }
+    /// Moves the camera to a pose, keeping its field of view in the
+    /// projection's range.
+    fn set_pose(&mut self, pose: Pose) {
+        self.orientation = pose.orientation;
+        self.fov = pose.fov;
+        self.change_fov(0.0);
+        self.change_zoom(pose.zoom - self.source.zoom);
+        self.rotate_viewrays(0.0, 0.0, 0.0);
+    }
+    /// Gets the longitude and latitude under a point of the overview given in
+    /// normalized device coordinates, matching `overview_ray` in `project.fs`.
+    fn overview_direction(ndc: [f32; 2], aspect: f32) -> Option<(f32, f32)> {
+        let lon = ndc[0] * f32::max(aspect / 2.0, 1.0) * PI;
+        let lat = ndc[1] * f32::max(2.0 / aspect, 1.0) * PI / 2.0;
+        if lon.abs() > PI || lat.abs() > PI / 2.0 {
+            None
+        } else {
+            Some((lon, lat))
+        }
+    }
+    /// Changes the field of view, keeping it in the projection's usable range.
+    fn change_fov(&mut self, delta: f32) {
+        let (min, max) = self.projection.fov_range(self.pannini_compression);
+        self.fov = (self.fov + delta).clamp(min, max);
+    }
+    /// Switches projection, jumping to its default field of view if the current
+    /// one is out of its range.
+    fn set_projection(&mut self, projection: Projection) {
+        self.projection = projection;
+        let (min, max) = projection.fov_range(self.pannini_compression);
+        if self.fov < min || self.fov > max {
+            self.fov = projection.default_fov();
+        }
+        self.rotate_viewrays(0.0, 0.0, 0.0);
+    }
+    fn change_pannini_compression(&mut self, delta: f32) {
+        self.pannini_compression = (self.pannini_compression + delta).clamp(0.0, 2.0);
+        self.change_fov(0.0);
+    }
+    /// Gets the orientation the per-pixel projections render from for an eye
+    /// turned by `eye_yaw`.
+    fn projection_matrix(&self, eye_yaw: f32) -> Mat3 {
+        self.projection_rotor(eye_yaw).into_matrix()
+    }
+    fn projection_rotor(&self, eye_yaw: f32) -> Rotor3 {
+        let orientation = if self.projection == Projection::LittlePlanet {
+            self.orientation * Rotor3::from_rotation_yz(-PI / 2.)
+        } else {
+            self.orientation
+        };
+        orientation * Rotor3::from_rotation_xz(-eye_yaw)
+    }
+    /// Gets the source space direction seen at screen point `p`, where the
+    /// view spans -1 to 1, or `None` where the projection doesn't cover it.
+    fn screen_ray(&self, p: [f32; 2]) -> Option<Vec3> {
+        self.projection
+            .camera_ray(p, self.fov, self.pannini_compression)
+            .map(|ray| self.projection_rotor(0.0) * ray)
+    }
+    /// Whether one camera fills the window, so window points map straight to
+    /// directions through [`screen_ray`](Self::screen_ray).
+    fn single_view(&self) -> bool {
+        !self.overview
+            && self.stereo.output != StereoOutput::SideBySide
+            && !self
+                .compare
+                .as_ref()
+                .is_some_and(|compare| compare.layout == CompareLayout::SideBySide)
+    }
+    /// Gets the screen point, where the view spans -1 to 1, showing source
+    /// space direction `ray`, or `None` where the projection can't place it.
+    fn screen_point(&self, ray: Vec3) -> Option<[f32; 2]> {
+        let cam = self.projection_rotor(0.0).reversed() * ray;
+        self.projection
+            .screen_point(cam, self.fov, self.pannini_compression)
+    }
+    /// Gets the source space rotation that turns the rectilinear `viewrays`
+    /// into an eye turned by `eye_yaw`.
+    fn eye_rotation_matrix(&self, eye_yaw: f32) -> Mat3 {
+        let eye = Rotor3::from_rotation_xz(-eye_yaw);
+        (self.orientation * eye * self.orientation.reversed()).into_matrix()
+    }
+    /// Changes the twin view zoom of every source on screen.
+    fn change_zoom(&mut self, delta: f32) {
+        self.source.zoom += delta;
+        if let Some(compare) = &mut self.compare {
+            compare.source.zoom += delta;
+        }
     }
     fn get_distance(&self) -> f32 {
         -0.5 / (self.fov / 2.0).tan()
     }
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let filename = args[1].as_str();
-    let mut twin_view = false;
-    let mut source_fov = PI;
-    if args[2] == "t" || args[2] == "twin" {
-        twin_view = true;
-    } else {
-        source_fov = args[2]
-            .parse::<f32>()
-            .expect("Invalid input for source field of view!")
-            * PI
-            / 180.;
+/// How much of the field of view a zoom-through narrows by.
+const ZOOM_THROUGH_DEPTH: f32 = 0.5;
+
+/// A scene fading out over the one that replaced it.
+struct Fade<'gl> {
+    texture: Texture<'gl>,
+    /// The scene's source, turned to undo any turn of the camera on arrival.
+    source: Source,
+    /// The camera the scene is still seen through.
+    view: Pose,
+    transition: Transition,
+    /// Seconds the whole transition takes.
+    duration: f32,
+    start: Instant,
+}
+
+impl Fade<'_> {
+    /// Gets how far through the transition it is, from 0 to 1.
+    fn progress(&self) -> f32 {
+        (self.start.elapsed().as_secs_f32() / self.duration).min(1.0)
+    }
+
+    /// Gets how opaque the scene still is.
+    fn opacity(&self) -> f32 {
+        1.0 - self.progress()
     }
-    // Setup the window
+
+    /// Gets what the fields of view of the scene being left and of the one
+    /// arriving are scaled by.
+    fn zoom(&self) -> (f32, f32) {
+        if self.transition != Transition::ZoomThrough {
+            return (1.0, 1.0);
+        }
+        let t = self.progress();
+        let t = t * t * (3.0 - 2.0 * t);
+        (
+            1.0 - ZOOM_THROUGH_DEPTH * t,
+            1.0 - ZOOM_THROUGH_DEPTH * (1.0 - t),
+        )
+    }
+
+    /// Draws the scene over `region` of the one arriving, which is `shown`.
+    fn draw(
+        &self,
+        renderer: &Renderer,
+        shown: &RotImage,
+        region: [i32; 4],
+        clip: [i32; 4],
+    ) -> Result<(), String> {
+        let mut leaving = shown.clone();
+        leaving.set_pose(Pose {
+            fov: self.view.fov * self.zoom().0,
+            ..self.view
+        });
+        renderer.upload_viewrays(&leaving);
+        let drawn = renderer.draw(
+            &leaving,
+            &self.source,
+            &self.texture,
+            region,
+            clip,
+            self.opacity(),
+        );
+        renderer.upload_viewrays(shown);
+        drawn
+    }
+}
+
+/// Parsed command line: `[options] [image]... [view type]`, or
+/// `[options] --tour file`.
+struct Args {
+    images: Vec<String>,
+    view: String,
+    stick_dead_zone: Option<f32>,
+    trigger_dead_zone: Option<f32>,
+    config: Option<String>,
+    bindings: Option<String>,
+    stereo: Option<StereoLayout>,
+    compare: Option<String>,
+    compare_view: Option<String>,
+    tour: Option<String>,
+    slideshow: Option<f32>,
+    transition: Option<Transition>,
+    export: Option<String>,
+    export_fps: f32,
+    export_size: Option<(u32, u32)>,
+    gl_debug: bool,
+    shader_dir: Option<String>,
+    headless: bool,
+    golden: Option<String>,
+    golden_update: bool,
+}
+
+fn parse_args() -> Args {
+    let mut positional = Vec::new();
+    let mut stick_dead_zone = None;
+    let mut trigger_dead_zone = None;
+    let mut config = None;
+    let mut bindings = None;
+    let mut stereo = None;
+    let mut compare = None;
+    let mut compare_view = None;
+    let mut tour = None;
+    let mut slideshow = None;
+    let mut transition = None;
+    let mut export = None;
+    let mut export_fps = 30.0;
+    let mut export_size = None;
+    let mut gl_debug = false;
+    let mut shader_dir = None;
+    let mut headless = false;
+    let mut golden = None;
+    let mut golden_update = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--dead-zone" => {
+                let value = args.next().expect("--dead-zone needs a value");
+                stick_dead_zone = Some(value.parse::<f32>().expect("Invalid dead zone!"));
+            }
+            "--trigger-dead-zone" => {
+                let value = args.next().expect("--trigger-dead-zone needs a value");
+                trigger_dead_zone = Some(value.parse::<f32>().expect("Invalid dead zone!"));
+            }
+            "--stereo" => {
+                let value = args.next().expect("--stereo needs a layout");
+                stereo = Some(
+                    StereoLayout::from_arg(&value)
+                        .expect("Stereo layout must be mono, tb (top-bottom) or lr (left-right)!"),
+                );
+            }
+            "--compare" => {
+                compare = Some(args.next().expect("--compare needs an image"));
+            }
+            "--compare-view" => {
+                compare_view = Some(args.next().expect("--compare-view needs a view type"));
+            }
+            "--tour" => {
+                tour = Some(args.next().expect("--tour needs a file"));
+            }
+            "--slideshow" => {
+                let value = args.next().expect("--slideshow needs a number of seconds");
+                slideshow = Some(value.parse::<f32>().expect("Invalid slideshow time!"));
+            }
+            "--transition" => {
+                let value = args.next().expect("--transition needs a kind");
+                transition = Some(
+                    Transition::from_arg(&value)
+                        .expect("Transition must be cut, crossfade or zoom!"),
+                );
+            }
+            "--export" => {
+                export = Some(args.next().expect("--export needs a file or directory"));
+            }
+            "--export-fps" => {
+                let value = args.next().expect("--export-fps needs a value");
+                export_fps = value.parse::<f32>().expect("Invalid frame rate!");
+                assert!(export_fps > 0.0, "The frame rate must be positive!");
+            }
+            "--export-size" => {
+                let value = args.next().expect("--export-size needs a size");
+                export_size = Some(
+                    value
+                        .split_once('x')
+                        .and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?)))
+                        .expect("Export size must look like 1920x1080!"),
+                );
+            }
+            "--gl-debug" => gl_debug = true,
+            "--headless" => headless = true,
+            "--golden" => {
+                golden = Some(args.next().expect("--golden needs a directory"));
+            }
+            "--golden-update" => golden_update = true,
+            "--shader-dir" => {
+                shader_dir = Some(args.next().expect("--shader-dir needs a directory"));
+            }
+            "--config" => {
+                config = Some(args.next().expect("--config needs a file"));
+            }
+            "--bindings" => {
+                bindings = Some(args.next().expect("--bindings needs a file"));
+            }
+            _ => positional.push(arg),
+        }
+    }
+    if positional.len() < 2 && tour.is_none() {
+        eprintln!(
+            "Usage: 360-photo-viewer [--config file] [--bindings file] [--dead-zone fraction] [--trigger-dead-zone fraction] [--stereo layout] [--compare image] [--compare-view view type] [--slideshow seconds] [--transition kind] [--export target] [--export-fps fps] [--export-size WxH] [--golden dir] [--golden-update] [--headless] [--gl-debug] [--shader-dir dir] ([image]... [view type] | --tour file)"
+        );
+        std::process::exit(1);
+    }
+    if export.is_some() && golden.is_some() {
+        eprintln!("--export and --golden can't be used together");
+        std::process::exit(1);
+    }
+    if golden_update && golden.is_none() {
+        eprintln!("--golden-update needs --golden to know where the golden images go");
+        std::process::exit(1);
+    }
+    if headless && export.is_none() && golden.is_none() {
+        eprintln!("--headless has no window, so it needs --export or --golden");
+        std::process::exit(1);
+    }
+    let view = positional.pop().unwrap_or_default();
+    Args {
+        images: positional,
+        view,
+        stick_dead_zone,
+        trigger_dead_zone,
+        config,
+        bindings,
+        stereo,
+        compare,
+        compare_view,
+        tour,
+        slideshow,
+        transition,
+        export,
+        export_fps,
+        export_size,
+        gl_debug,
+        shader_dir,
+        headless,
+        golden,
+        golden_update,
+    }
+}
+
+/// What the GL context draws for: a window, or with `--headless` nothing but
+/// offscreen framebuffers.
+enum Display {
+    Window {
+        sdl: Sdl,
+        win: GlWindow,
+    },
+    #[cfg(feature = "headless")]
+    Headless(Headless),
+}
+
+fn open_window(config: &Config, title: &str) -> Display {
     let sdl = Sdl::init(InitFlags::EVERYTHING);
     sdl.set_gl_profile(GlProfile::Core).unwrap();
     sdl.set_gl_context_major_version(3).unwrap();
     sdl.set_gl_context_minor_version(3).unwrap();
+    if config.window.gl_debug {
+        sdl.set_gl_context_flags(GlContextFlags::DEBUG).unwrap();
+    }
 
     let win = sdl
         .create_gl_window(CreateWinArgs {
-            title: filename,
+            title,
             resizable: true,
-            width: 800,
-            height: 800,
+            width: config.window.width,
+            height: config.window.height,
             ..Default::default()
         })
         .expect("couldn't make a window and context");
 
     win.set_swap_interval(GlSwapInterval::Vsync)
         .expect("Failed to set swap interval");
+    Display::Window { sdl, win }
+}
 
-    unsafe {
-        load_global_gl(&|f_name| win.get_proc_address(f_name));
-    }
+#[cfg(feature = "headless")]
+fn open_headless(debug: bool) -> Display {
+    let headless = Headless::new(debug)
+        .unwrap_or_else(|e| panic!("Could not make a headless GL context: {}", e));
+    Display::Headless(headless)
+}
 
-    gl_safe::clear_color(0.0, 0.0, 0.0, 1.0);
+#[cfg(not(feature = "headless"))]
+fn open_headless(_debug: bool) -> Display {
+    eprintln!("--headless needs the viewer built with `--features headless`");
+    std::process::exit(1);
+}
+
+fn main() {
+    let args = parse_args();
+    let mut config = Config::load(
+        args.config.as_deref().map(Path::new),
+        args.bindings.as_deref().map(Path::new),
+    )
+    .unwrap_or_else(|e| panic!("Could not load config: {}", e));
+    if let Some(stick_dead_zone) = args.stick_dead_zone {
+        config.gamepad.stick_dead_zone = stick_dead_zone;
+    }
+    if let Some(trigger_dead_zone) = args.trigger_dead_zone {
+        config.gamepad.trigger_dead_zone = trigger_dead_zone;
+    }
+    config.gamepad.stick_dead_zone = config.gamepad.stick_dead_zone.clamp(0.0, 0.99);
+    config.gamepad.trigger_dead_zone = config.gamepad.trigger_dead_zone.clamp(0.0, 0.99);
+    if let Some(dwell) = args.slideshow {
+        config.slideshow.enabled = true;
+        config.slideshow.dwell = dwell;
+    }
+    if let Some(transition) = args.transition {
+        config.slideshow.transition = transition;
+    }
+    config.window.gl_debug |= args.gl_debug;
+    let tour = match &args.tour {
+        Some(path) => {
+            Tour::load(Path::new(path)).unwrap_or_else(|e| panic!("Could not load tour: {}", e))
+        }
+        None => Tour::from_images(&args.images, &args.view),
+    };
+    let mut current_scene = tour.start_index();
+    let scene = &tour.scenes[current_scene];
+    let source = Source::from_view_arg(&scene.view).unwrap_or_else(|e| panic!("{}", e));
+    let compare_source = args.compare_view.as_deref().map_or(source, |view| {
+        Source::from_view_arg(view).unwrap_or_else(|e| panic!("--compare-view: {}", e))
+    });
+    let display = if args.headless {
+        open_headless(config.window.gl_debug)
+    } else {
+        open_window(&config, &scene.name)
+    };
+    let gl = match &display {
+        Display::Window { win, .. } => Context::load(win, config.window.gl_debug),
+        #[cfg(feature = "headless")]
+        Display::Headless(headless) => headless.load(config.window.gl_debug),
+    };
+    match &display {
+        #[cfg(feature = "headless")]
+        Display::Headless(headless) => {
+            let (major, minor) = headless.version;
+            eprintln!(
+                "Drawing with {} through EGL {}.{}",
+                gl.renderer_name(),
+                major,
+                minor
+            );
+        }
+        _ if config.window.gl_debug => eprintln!("Drawing with {}", gl.renderer_name()),
+        _ => {}
+    }
+    let [r, g, b, a] = config.window.clear_color;
+    gl.clear_color(r, g, b, a);
+    gl.enable_blending();
 
-    let vao = VertexArray::new().expect("Couldn't make a VAO");
-    vao.bind();
     let mut image: RotImage = RotImage {
-        source_fov,
-        twin_view,
+        fov: config.camera.fov.to_radians(),
+        stereo: Stereo {
+            layout: args.stereo.unwrap_or(config.stereo.layout),
+            ipd: config.stereo.ipd,
+            convergence: config.stereo.convergence.to_radians(),
+            ..Default::default()
+        },
+        source,
+        compare: args
+            .compare
+            .as_ref()
+            .map(|_| Comparison::new(compare_source)),
+        overlays: Overlays::new(&config.overlays),
         ..Default::default()
     };
-    image.rotate_viewrays(0.0, 0.0);
-
-    let vbo = Buffer::new().expect("Couldn't make a VBO");
-    vbo.bind(gl_safe::BufferType::Array);
-    gl_safe::buffer_data(
-        gl_safe::BufferType::Array,
-        bytemuck::cast_slice(&image.viewrays),
-        GL_DYNAMIC_DRAW,
-    );
-
-    unsafe {
-        glVertexAttribPointer(
-            0,
-            2,
-            GL_FLOAT,
-            0,
-            size_of::<Vertex>().try_into().unwrap(),
-            0 as *const _,
-        );
-        glEnableVertexAttribArray(0);
-        glVertexAttribPointer(
-            1,
-            3,
-            GL_FLOAT,
-            0,
-            size_of::<Vertex>().try_into().unwrap(),
-            (size_of::<f32>() * 2) as *const c_void, //[(size_of::<f32>() as c_int) * 3].as_ptr() as *const _,
-        );
-        glEnableVertexAttribArray(1);
-    }
-    // Create Program
-    let frag_shader = open_file("src/shd/project.fs").expect("Unable to open fragment shader");
-    let vert_shader = open_file("src/shd/project.vs").expect("Unable to open vertex shader");
-    let shader_program = ShaderProgram::from_vert_frag(&vert_shader, &frag_shader).unwrap();
-    shader_program.use_program();
-    println!("Shader compliation status: {}", shader_program.info_log());
-
-    let texture: gl_safe::Texture;
-    unsafe {
-        texture = gl_safe::Texture::new();
+    image.overlays.north = metadata::pose_heading(&scene.image).map(f32::to_radians);
+    let mut annotations = Annotations::load(&scene.image);
+    let mut bookmarks = Bookmarks::load(&scene.image);
+    let mut camera_path = CameraPath::load(&scene.image);
+    image.change_fov(0.0);
+    image.apply_scene(scene, true);
+
+    let mut shaders = match &args.shader_dir {
+        Some(dir) => Shaders::from_dir(dir.into()),
+        None => Shaders::embedded(),
+    };
+    let mut renderer = Renderer::new(&gl, &shaders, &image, config.hud.scale);
+    let mut hud = Hud::new(&gl, &shaders, &config.hud);
+    let mut minimap = Minimap::new(&gl, &shaders, &config.hud);
+    let mut texture = Texture::new(&gl).expect("Couldn't make a texture");
+    let mut image_size = texture
+        .load(&scene.image)
+        .expect("Could not open image and load texture!");
+    let mut fade: Option<Fade> = None;
+    let mut slideshow = config
+        .slideshow
+        .enabled
+        .then(|| Slideshow::new(&config.slideshow));
+    let compare_texture = args.compare.as_ref().map(|compare| {
+        let texture = Texture::new(&gl).expect("Couldn't make a texture");
         texture
-            .load(&Path::new(filename))
-            .expect("Could not open image and load texture!");
-        glActiveTexture(GL_TEXTURE0);
-        glBindTexture(GL_TEXTURE_2D, texture.0);
-        glGenerateMipmap(GL_TEXTURE_2D);
-        glBlendFunc(GL_SRC_ALPHA, GL_ONE_MINUS_SRC_ALPHA);
-        glEnable(GL_BLEND);
-    }
-    let scalar_location = get_shader_variable("scalar", shader_program.0);
-    let zoom_location = get_shader_variable("zoom", shader_program.0);
-    let twin_view_location = get_shader_variable("twin_view", shader_program.0);
-    let mut controller: CameraController = Default::default();
-
-    unsafe {
-        glUniform1i(twin_view_location, if image.twin_view { 1 } else { 0 });
-    }
-    loop {
-        let (update_camera, exit) = controller.handle_inputs(&sdl, &mut image);
-        if exit {
-            break;
-        }
-        unsafe {
-            glClear(GL_COLOR_BUFFER_BIT | GL_DEPTH_BUFFER_BIT);
-            if update_camera {
-                vbo.bind(gl_safe::BufferType::Array);
-                gl_safe::buffer_data(
-                    gl_safe::BufferType::Array,
-                    bytemuck::cast_slice(&image.viewrays),
-                    GL_DYNAMIC_DRAW,
-                );
-                Buffer::clear_binding(gl_safe::BufferType::Array);
+            .load(Path::new(compare))
+            .expect("Could not open comparison image and load texture!");
+        texture
+    });
+    let mut controller = CameraController::new(&config);
+    controller
+        .bindings
+        .resolve()
+        .unwrap_or_else(|e| panic!("Could not load key bindings: {}", e));
+
+    if let Some(target) = args.export.as_ref().or(args.golden.as_ref()) {
+        let (width, height) = args
+            .export_size
+            .unwrap_or((config.window.width as u32, config.window.height as u32));
+        // Golden images always live in a directory, whatever its name.
+        let export = if args.golden.is_some() {
+            Ok(Export {
+                target: PathBuf::from(target),
+                format: if args.golden_update {
+                    Format::GoldenUpdate
+                } else {
+                    Format::Golden
+                },
+                fps: args.export_fps,
+                width,
+                height,
+            })
+        } else {
+            Export::new(target, args.export_fps, width, height)
+        };
+        let export = export.unwrap_or_else(|e| {
+            eprintln!("Export failed: {}", e);
+            std::process::exit(1);
+        });
+        // Without a camera path, export one turn of the auto-rotate spin.
+        let start = image.pose();
+        let spin = AutoRotate::new(&config.auto_rotate);
+        let duration = if camera_path.keyframes.is_empty() {
+            spin.turn_seconds().unwrap_or_else(|| {
+                eprintln!("Nothing to export: no camera path and an auto-rotate speed of 0");
+                std::process::exit(1);
+            })
+        } else {
+            camera_path.duration()
+        };
+        let pose_at = |t| {
+            camera_path
+                .pose_at(t)
+                .unwrap_or_else(|| spin.steady_pose_at(&start, t))
+        };
+        match export.render(&gl, duration, pose_at, &mut image, &renderer, &texture) {
+            Ok(frames) if export.format == Format::Golden => {
+                println!("{} frames match {}", frames, target)
+            }
+            Ok(frames) if export.format == Format::GoldenUpdate => {
+                println!("Recorded {} golden images in {}", frames, target)
+            }
+            Ok(frames) => println!("Exported {} frames to {}", frames, target),
+            Err(e) => {
+                eprintln!("Export failed: {}", e);
+                std::process::exit(1);
             }
-            glUniform1f(scalar_location, image.get_scalar());
-            glUniform1f(zoom_location, image.zoom);
-            glDrawArrays(GL_TRIANGLE_FAN, 0, 4);
-            win.swap_window();
         }
+        return;
     }
-}
 
-fn open_file(file_name: &str) -> Result<String, std::io::Error> {
-    let mut file = File::open(file_name)?;
-    let mut file_contents = String::new();
-    file.read_to_string(&mut file_contents)?;
-    Ok(file_contents)
-}
+    let (sdl, win) = match &display {
+        Display::Window { sdl, win } => (sdl, win),
+        #[cfg(feature = "headless")]
+        Display::Headless(_) => unreachable!("headless runs always export"),
+    };
+    let (mut drawable_width, mut drawable_height) = win.get_drawable_size();
 
-fn get_shader_variable(str: &str, program_id: u32) -> i32 {
-    unsafe {
-        let c_name = CString::new(str).expect("Convert to c-string");
-        let location = glGetUniformLocation(program_id, c_name.as_ptr() as *const _);
-        let error = glGetError();
-        if error != GL_NO_ERROR {
-            panic!("Failed to find scalar location! {:?}", error);
+    let mut last_frame = Instant::now();
+    // Printed once rather than every frame it happens again.
+    let mut last_draw_error = None;
+    'main: loop {
+        // The scene to switch to, and for hotspots the yaw to turn by.
+        let mut next_scene = None;
+        let scene = &tour.scenes[current_scene];
+        for action in controller.handle_inputs(sdl, &mut image, &mut annotations, scene) {
+            match action {
+                Action::Exit => break 'main,
+                Action::ToggleHud => hud.visible = !hud.visible,
+                Action::ToggleMinimap => minimap.visible = !minimap.visible,
+                Action::ToggleHelp => hud.help = !hud.help,
+                Action::Resized => {
+                    (drawable_width, drawable_height) = win.get_drawable_size();
+                    gl.viewport([0, 0, drawable_width, drawable_height]);
+                }
+                Action::NextImage | Action::PreviousImage if tour.scenes.len() > 1 => {
+                    let step = if action == Action::NextImage {
+                        1
+                    } else {
+                        tour.scenes.len() - 1
+                    };
+                    next_scene = Some(((current_scene + step) % tour.scenes.len(), None));
+                }
+                Action::NextImage | Action::PreviousImage => {}
+                Action::SaveBookmark(slot) => {
+                    bookmarks.set(slot, &image.pose());
+                    println!("Saved bookmark {}", slot);
+                }
+                Action::RecallBookmark(slot) => match bookmarks.get(slot) {
+                    Some(pose) => controller.glide_to(&image, pose),
+                    None => println!("No bookmark {} for this image", slot),
+                },
+                Action::AddKeyframe => {
+                    camera_path.add(&image.pose());
+                    let count = camera_path.keyframes.len();
+                    println!("Keyframe {} at {:.2}s", count, camera_path.duration());
+                }
+                Action::ClearPath => {
+                    camera_path.clear();
+                    println!("Cleared the camera path");
+                }
+                Action::PlayPath => {
+                    if controller.playing_path() {
+                        controller.stop_animation();
+                    } else if camera_path.keyframes.is_empty() {
+                        println!("No camera path for this image");
+                    } else {
+                        controller.play(camera_path.clone());
+                    }
+                }
+                Action::FollowHotspot(index) => {
+                    let hotspot = &scene.hotspots[index];
+                    // Tour::load checked that every hotspot leads somewhere.
+                    let target = tour.scene_index(&hotspot.to).unwrap();
+                    next_scene = Some((target, Some(hotspot.yaw_offset.to_radians())));
+                }
+            }
+        }
+        if let Some(slideshow) = &slideshow {
+            if next_scene.is_none() && tour.scenes.len() > 1 && slideshow.due() {
+                next_scene = Some(((current_scene + 1) % tour.scenes.len(), None));
+            }
+        }
+        let mut scene_changed = false;
+        if let Some((index, yaw_offset)) = next_scene {
+            let scene = &tour.scenes[index];
+            let next_texture = Texture::new(&gl).expect("Couldn't make a texture");
+            match next_texture.load(&scene.image) {
+                Ok(size) => {
+                    let previous_texture = std::mem::replace(&mut texture, next_texture);
+                    let mut previous_source = image.source;
+                    if let Some(yaw_offset) = yaw_offset {
+                        image.turn(yaw_offset);
+                        previous_source.yaw -= yaw_offset;
+                    }
+                    // Hotspots fade over the tour's crossfade time, and
+                    // everything else uses the slideshow's transition.
+                    let (transition, duration) = match (yaw_offset, &slideshow) {
+                        (Some(_), _) => (Transition::Crossfade, tour.crossfade),
+                        (None, Some(slideshow)) => {
+                            (slideshow.transition, slideshow.transition_seconds)
+                        }
+                        (None, None) => (Transition::Cut, 0.0),
+                    };
+                    fade = (transition != Transition::Cut && duration > 0.0).then(|| Fade {
+                        texture: previous_texture,
+                        source: previous_source,
+                        view: image.pose(),
+                        transition,
+                        duration,
+                        start: Instant::now(),
+                    });
+                    image.source = Source {
+                        zoom: image.source.zoom,
+                        ..Source::from_view_arg(&scene.view)
+                            .expect("tours check their scenes' views when loading")
+                    };
+                    image.apply_scene(scene, yaw_offset.is_none());
+                    current_scene = index;
+                    scene_changed = true;
+                    controller.stop_animation();
+                    if let Some(slideshow) = &mut slideshow {
+                        slideshow.restart();
+                    }
+                    image_size = size;
+                    image.overlays.north =
+                        metadata::pose_heading(&scene.image).map(f32::to_radians);
+                    annotations = Annotations::load(&scene.image);
+                    bookmarks = Bookmarks::load(&scene.image);
+                    camera_path = CameraPath::load(&scene.image);
+                    win.set_title(&scene.name);
+                }
+                Err(e) => println!("Could not load {}: {}", scene.image.display(), e),
+            }
+        }
+        let scene = &tour.scenes[current_scene];
+        // Keep uploading rays through a fade and on the frame after it, as
+        // fades draw other views with them.
+        let fading = fade.is_some();
+        if fade.as_ref().is_some_and(|fade| fade.opacity() <= 0.0) {
+            fade = None;
+        }
+        let now = Instant::now();
+        // Clamp the step so a stalled frame doesn't fling the camera.
+        let frame_time = now.duration_since(last_frame).as_secs_f32();
+        hud.tick(frame_time);
+        if shaders.changed() {
+            renderer.reload(&gl, &shaders);
+            hud.reload(&gl, &shaders);
+            minimap.reload(&gl, &shaders);
+        }
+        let dt = frame_time.min(0.1);
+        last_frame = now;
+        let update_camera = controller.update(dt, &mut image) || scene_changed || fading;
+        // A zoom-through zooms out of the arriving scene.
+        let arriving = fade.as_ref().map(|fade| {
+            let mut arriving = image.clone();
+            arriving.set_pose(Pose {
+                fov: image.fov * fade.zoom().1,
+                ..image.pose()
+            });
+            arriving
+        });
+        let shown = arriving.as_ref().unwrap_or(&image);
+        gl.clear();
+        if update_camera {
+            renderer.upload_viewrays(shown);
+        }
+        let full = [0, 0, drawable_width, drawable_height];
+        let mut drawn = Ok(());
+        match (&image.compare, &compare_texture) {
+            (Some(compare), Some(compare_texture)) if !image.overview => {
+                for pane in compare.panes(drawable_width, drawable_height) {
+                    let (source, texture) = if pane.second {
+                        (&compare.source, compare_texture)
+                    } else {
+                        (&image.source, &texture)
+                    };
+                    drawn = drawn.and(renderer.draw(
+                        shown,
+                        source,
+                        texture,
+                        pane.region,
+                        pane.clip,
+                        1.0,
+                    ));
+                    if let (Some(fade), false) = (&fade, pane.second) {
+                        drawn = drawn.and(fade.draw(&renderer, shown, pane.region, pane.clip));
+                    }
+                }
+                if compare.layout == CompareLayout::Wipe {
+                    let x = compare.divider_x(drawable_width);
+                    gl.fill_rect([x - 1, 0, 2, drawable_height], [1.0, 1.0, 1.0, 1.0]);
+                }
+            }
+            _ => {
+                drawn = renderer.draw(shown, &image.source, &texture, full, full, 1.0);
+                if let Some(fade) = &fade {
+                    drawn = drawn.and(fade.draw(&renderer, shown, full, full));
+                }
+            }
+        }
+        drawn = drawn.and(minimap.draw(
+            &image,
+            &texture,
+            image_size,
+            drawable_width,
+            drawable_height,
+        ));
+        let (width, height) = image_size;
+        let info = ImageInfo {
+            name: &scene.name,
+            width,
+            height,
+            index: current_scene,
+            count: tour.scenes.len(),
+        };
+        drawn = drawn.and(hud.draw(
+            &image,
+            &annotations,
+            &scene.hotspots,
+            &info,
+            &controller.bindings,
+            drawable_width,
+            drawable_height,
+        ));
+        match drawn {
+            Err(e) if last_draw_error.as_ref() != Some(&e) => {
+                eprintln!("Drawing failed: {}", e);
+                last_draw_error = Some(e);
+            }
+            Err(_) => {}
+            Ok(()) => last_draw_error = None,
         }
-        location
+        gl.viewport([0, 0, drawable_width, drawable_height]);
+        win.swap_window();
     }
 }
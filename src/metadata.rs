@@ -0,0 +1,38 @@
+//! Reads what little the viewer needs from image metadata.
+
+use std::{fs::File, io::Read, path::Path};
+
+/// How far into a file to look for XMP. JPEGs keep it in their first segments.
+const XMP_SEARCH_BYTES: u64 = 1 << 20;
+
+/// Gets the compass heading in degrees of the image centre from the XMP
+/// `GPano:PoseHeadingDegrees` property, if the file has one.
+pub fn pose_heading(path: &Path) -> Option<f32> {
+    let mut head = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(XMP_SEARCH_BYTES)
+        .read_to_end(&mut head)
+        .ok()?;
+    xmp_number(&String::from_utf8_lossy(&head), "GPano:PoseHeadingDegrees")
+}
+
+/// Finds a numeric XMP property written either as an attribute
+/// (`name="12.5"`) or as an element (`<name>12.5</name>`).
+fn xmp_number(text: &str, name: &str) -> Option<f32> {
+    let mut rest = text;
+    while let Some(start) = rest.find(name) {
+        rest = &rest[start + name.len()..];
+        let value = rest
+            .strip_prefix("=\"")
+            .or_else(|| rest.strip_prefix("='"))
+            .or_else(|| rest.strip_prefix('>'));
+        if let Some(value) = value {
+            let end = value.find(['"', '\'', '<'])?;
+            if let Ok(number) = value[..end].trim().parse() {
+                return Some(number);
+            }
+        }
+    }
+    None
+}
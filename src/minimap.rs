@@ -0,0 +1,227 @@
+use gl33::gl_enumerations::*;
+use std::mem::take;
+use ultraviolet::Vec2;
+
+use crate::config::HudConfig;
+use crate::gl_safe::{self, Buffer, Context, Primitive, ShaderProgram, Texture, VertexArray};
+use crate::hud::{self, HudVertex};
+use crate::shaders::{self, Shaders};
+use crate::RotImage;
+
+/// Screen points sampled along each edge of the view to trace its outline.
+const EDGE_SAMPLES: usize = 32;
+/// Texture coordinate jump between neighbouring samples that means the
+/// outline crossed a seam of the source, such as between twin fisheyes.
+const SEAM_JUMP: f32 = 0.25;
+
+const FRAME_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const FOOTPRINT_COLOR: [f32; 4] = [1.0, 0.85, 0.0, 1.0];
+const WHITE: [f32; 4] = [1.0; 4];
+
+/// Font pixels between the window edge and the inset.
+const MARGIN: i32 = 4;
+
+/// A small inset of the raw source image in the bottom right corner, with
+/// the outline of what the camera currently sees drawn onto it.
+pub struct Minimap<'gl> {
+    gl: &'gl Context<'gl>,
+    pub visible: bool,
+    /// Screen pixels per HUD font pixel, so the inset's lines match the text.
+    scale: i32,
+    vao: VertexArray<'gl>,
+    vbo: Buffer<'gl>,
+    program: ShaderProgram<'gl>,
+    vertices: Vec<HudVertex>,
+}
+
+impl<'gl> Minimap<'gl> {
+    /// Builds the inset's program.
+    pub fn new(gl: &'gl Context<'_>, shaders: &Shaders, config: &HudConfig) -> Self {
+        let (vao, vbo) = hud::vertex_array(gl);
+        let program =
+            shaders.build_or_embedded(|shaders| shaders.program(gl, "text.vs", &["minimap.fs"]));
+        Self {
+            gl,
+            visible: config.minimap,
+            scale: config.scale.max(1),
+            vao,
+            vbo,
+            program,
+            vertices: Vec::new(),
+        }
+    }
+
+    /// Rebuilds the inset's program after its sources changed, keeping the
+    /// old one if the new sources don't compile.
+    pub fn reload(&mut self, gl: &'gl Context<'_>, shaders: &Shaders) {
+        shaders::swap_in(
+            &mut self.program,
+            shaders.program(gl, "text.vs", &["minimap.fs"]),
+        );
+    }
+
+    /// Draws the inset over a `width` by `height` window, showing `texture`
+    /// of `texture_size` pixels as the image's source. Fails if the inset's
+    /// shaders lack a uniform it sets.
+    pub fn draw(
+        &mut self,
+        image: &RotImage,
+        texture: &Texture,
+        texture_size: (u32, u32),
+        width: i32,
+        height: i32,
+    ) -> Result<(), String> {
+        if !self.visible {
+            return Ok(());
+        }
+        let (texture_width, texture_height) = texture_size;
+        let aspect = texture_width as f32 / texture_height.max(1) as f32;
+        let inset_height = (width.min(height) / 4) as f32;
+        let inset_width = (inset_height * aspect).min(width as f32 / 2.0);
+        let inset_height = inset_width / aspect;
+        let margin = (MARGIN * self.scale) as f32;
+        let left = width as f32 - margin - inset_width;
+        let top = height as f32 - margin - inset_height;
+        let border = self.scale as f32 * 2.0;
+
+        self.vertices.clear();
+        self.push_rect(
+            [
+                left - border,
+                top - border,
+                inset_width + 2.0 * border,
+                inset_height + 2.0 * border,
+            ],
+            [0.0, 0.0, 1.0, 1.0],
+            FRAME_COLOR,
+        );
+        self.push_rect(
+            [left, top, inset_width, inset_height],
+            [0.0, 0.0, 1.0, 1.0],
+            WHITE,
+        );
+        let to_inset = |[u, v]: [f32; 2]| [left + u * inset_width, top + v * inset_height];
+        let line_width = self.scale as f32;
+        for line in footprint(image) {
+            for pair in line.windows(2) {
+                self.push_segment(to_inset(pair[0]), to_inset(pair[1]), line_width);
+            }
+        }
+        if let Some(centre) = source_uv(image, [0.0, 0.0]) {
+            let [x, y] = to_inset(centre);
+            let size = line_width * 3.0;
+            self.push_rect(
+                [x - size / 2.0, y - size / 2.0, size, size],
+                [0.0; 4],
+                FOOTPRINT_COLOR,
+            );
+        }
+
+        let screen_size = Vec2::new(width as f32, height as f32);
+        let program = self.program.use_program();
+        program.set_uniform("screen_size", screen_size)?;
+        self.vao.bind();
+        self.vbo.bind(gl_safe::BufferType::Array);
+        gl_safe::buffer_data(
+            gl_safe::BufferType::Array,
+            bytemuck::cast_slice(&self.vertices),
+            GL_STREAM_DRAW,
+        );
+        let gl = self.gl;
+        gl.viewport([0, 0, width, height]);
+        gl.active_texture(0);
+        texture.bind();
+        // The frame, then the image, then the outline over it.
+        program.set_uniform("textured", false)?;
+        gl.draw_arrays(Primitive::Triangles, 0, 6);
+        program.set_uniform("textured", true)?;
+        gl.draw_arrays(Primitive::Triangles, 6, 6);
+        program.set_uniform("textured", false)?;
+        gl.draw_arrays(Primitive::Triangles, 12, self.vertices.len() as i32 - 12);
+        Ok(())
+    }
+
+    /// Adds an `[x, y, width, height]` pixel rectangle, measured from the top
+    /// left, with `[left, top, right, bottom]` texture coordinates.
+    fn push_rect(&mut self, rect: [f32; 4], uv: [f32; 4], color: [f32; 4]) {
+        let [x, y, width, height] = rect;
+        let [u0, v0, u1, v1] = uv;
+        let corner = |px: f32, py: f32, u: f32, v: f32| HudVertex::new([px, py], [u, v], color);
+        let top_left = corner(x, y, u0, v0);
+        let top_right = corner(x + width, y, u1, v0);
+        let bottom_left = corner(x, y + height, u0, v1);
+        let bottom_right = corner(x + width, y + height, u1, v1);
+        self.vertices.extend_from_slice(&[
+            top_left,
+            bottom_left,
+            top_right,
+            top_right,
+            bottom_left,
+            bottom_right,
+        ]);
+    }
+
+    /// Adds a line `width` pixels thick from `a` to `b`.
+    fn push_segment(&mut self, a: [f32; 2], b: [f32; 2], width: f32) {
+        let (dx, dy) = (b[0] - a[0], b[1] - a[1]);
+        let length = dx.hypot(dy);
+        if length < 1e-3 {
+            return;
+        }
+        let (nx, ny) = (-dy / length * width / 2.0, dx / length * width / 2.0);
+        let corner = |x: f32, y: f32| HudVertex::new([x, y], [0.0; 2], FOOTPRINT_COLOR);
+        let (a0, a1) = (corner(a[0] + nx, a[1] + ny), corner(a[0] - nx, a[1] - ny));
+        let (b0, b1) = (corner(b[0] + nx, b[1] + ny), corner(b[0] - nx, b[1] - ny));
+        self.vertices.extend_from_slice(&[a0, a1, b0, b0, a1, b1]);
+    }
+}
+
+/// Gets where on the source texture the image shows screen point `p`, for
+/// the left eye of a stereo source.
+fn source_uv(image: &RotImage, p: [f32; 2]) -> Option<[f32; 2]> {
+    let ray = image.screen_ray(p)?;
+    let uv = image.source.direction_uv(ray)?;
+    Some(image.stereo.layout.eye_uv(uv, 0))
+}
+
+/// Traces the edge of the view onto the source texture, inverting the
+/// projection one screen point at a time. The outline is split into separate
+/// lines of texture coordinates wherever it leaves the source or jumps across
+/// a seam.
+fn footprint(image: &RotImage) -> Vec<Vec<[f32; 2]>> {
+    let step = 2.0 / EDGE_SAMPLES as f32;
+    let edges: [([f32; 2], [f32; 2]); 4] = [
+        ([-1.0, -1.0], [step, 0.0]),
+        ([1.0, -1.0], [0.0, step]),
+        ([1.0, 1.0], [-step, 0.0]),
+        ([-1.0, 1.0], [0.0, -step]),
+    ];
+    let points = edges
+        .iter()
+        .flat_map(|&(start, delta)| {
+            (0..EDGE_SAMPLES).map(move |i| {
+                let t = i as f32;
+                [start[0] + delta[0] * t, start[1] + delta[1] * t]
+            })
+        })
+        .chain(Some([-1.0, -1.0]));
+
+    let mut lines = Vec::new();
+    let mut line: Vec<[f32; 2]> = Vec::new();
+    for p in points {
+        match source_uv(image, p) {
+            Some(uv) => {
+                if let Some(last) = line.last() {
+                    if (uv[0] - last[0]).hypot(uv[1] - last[1]) > SEAM_JUMP {
+                        lines.push(take(&mut line));
+                    }
+                }
+                line.push(uv);
+            }
+            None => lines.push(take(&mut line)),
+        }
+    }
+    lines.push(line);
+    lines.retain(|line| line.len() > 1);
+    lines
+}
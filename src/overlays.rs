@@ -0,0 +1,46 @@
+use std::f32::consts::PI;
+
+use crate::config::OverlayConfig;
+
+/// Orientation aids drawn over the view by `overlay.fs`.
+#[derive(Debug, Clone, Default)]
+pub struct Overlays {
+    /// Lines every 15 degrees of latitude and longitude.
+    pub grid: bool,
+    pub horizon: bool,
+    /// Heading strip across the top of the view.
+    pub compass: bool,
+    /// Compass heading of the source image's centre in radians, when its
+    /// metadata records one.
+    pub north: Option<f32>,
+}
+
+impl Overlays {
+    pub fn new(config: &OverlayConfig) -> Self {
+        Self {
+            grid: config.grid,
+            horizon: config.horizon,
+            compass: config.compass,
+            north: None,
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        self.grid || self.horizon || self.compass
+    }
+
+    /// Gets what to add to a longitude, as used by the overview, to get the
+    /// heading the compass shows. Without a known north this is zero and the
+    /// compass shows yaw.
+    pub fn heading_offset(&self) -> f32 {
+        // The image centre looks along `+z`, which is at longitude -90°.
+        self.north.map_or(0.0, |north| north + PI / 2.0)
+    }
+
+    /// Gets the compass heading in radians, from 0 to 2π, of a longitude
+    /// when north is known.
+    pub fn heading(&self, lon: f32) -> Option<f32> {
+        self.north
+            .map(|_| (lon + self.heading_offset()).rem_euclid(2.0 * PI))
+    }
+}
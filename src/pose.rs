@@ -0,0 +1,85 @@
+//! Camera poses and smooth moves between them.
+
+use std::f32::consts::PI;
+use ultraviolet::{Lerp, Rotor3, Slerp, Vec3};
+
+/// Where the camera looks and how far it's zoomed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    /// Maps camera space rays (looking down `-z`) into source space.
+    pub orientation: Rotor3,
+    /// Field of view in radians.
+    pub fov: f32,
+    /// Twin view zoom.
+    pub zoom: f32,
+}
+
+impl Pose {
+    /// Builds a pose from `[yaw, pitch, roll]` in radians, as
+    /// [`angles`](Self::angles) gives them.
+    pub fn from_angles([yaw, pitch, roll]: [f32; 3], fov: f32, zoom: f32) -> Self {
+        Self {
+            orientation: Rotor3::from_rotation_xz(yaw - PI / 2.)
+                * Rotor3::from_rotation_yz(pitch)
+                * Rotor3::from_rotation_xy(roll),
+            fov,
+            zoom,
+        }
+    }
+
+    /// Gets the camera's `[yaw, pitch, roll]` in radians. Yaw and pitch are
+    /// the longitude and latitude it looks at, as in the overview.
+    pub fn angles(&self) -> [f32; 3] {
+        let forward = self.orientation * -Vec3::unit_z();
+        let right = self.orientation * Vec3::unit_x();
+        let up = self.orientation * Vec3::unit_y();
+        [
+            (-forward.z).atan2(-forward.x),
+            forward.y.clamp(-1.0, 1.0).asin(),
+            right.y.atan2(up.y),
+        ]
+    }
+
+    /// Gets the pose `t` of the way to `other`, turning along the shortest arc.
+    pub fn interpolate(&self, other: &Pose, t: f32) -> Pose {
+        Pose {
+            orientation: self.orientation.slerp(other.orientation, t).normalized(),
+            fov: self.fov.lerp(other.fov, t),
+            zoom: self.zoom.lerp(other.zoom, t),
+        }
+    }
+}
+
+/// An eased move from one pose to another.
+#[derive(Debug, Clone)]
+pub struct Glide {
+    from: Pose,
+    to: Pose,
+    elapsed: f32,
+    /// Seconds the whole move takes.
+    duration: f32,
+}
+
+impl Glide {
+    pub fn new(from: Pose, to: Pose, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            elapsed: 0.0,
+            duration,
+        }
+    }
+
+    /// Moves on by `dt` seconds, returning the pose to show.
+    pub fn advance(&mut self, dt: f32) -> Pose {
+        self.elapsed += dt;
+        let t = (self.elapsed / self.duration.max(1e-6)).min(1.0);
+        // Ease in and out.
+        let t = t * t * (3.0 - 2.0 * t);
+        self.from.interpolate(&self.to, t)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
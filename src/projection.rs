@@ -0,0 +1,239 @@
+use serde::Deserialize;
+use std::f32::consts::PI;
+use ultraviolet::Vec3;
+
+/// How view directions are laid out on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Projection {
+    /// Pinhole camera. Straight lines stay straight but wide views stretch.
+    Rectilinear,
+    /// Conformal, keeps shapes round at wide fields of view.
+    Stereographic,
+    /// Stereographic looking 90° below the view, turning the ground into a
+    /// tiny world.
+    LittlePlanet,
+    /// Equidistant fisheye, angle from the centre is proportional to radius.
+    Fisheye,
+    /// Keeps vertical lines straight while compressing the horizontal.
+    Pannini,
+}
+
+impl Projection {
+    pub const ALL: [Projection; 5] = [
+        Projection::Rectilinear,
+        Projection::Stereographic,
+        Projection::LittlePlanet,
+        Projection::Fisheye,
+        Projection::Pannini,
+    ];
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|&p| p == self).unwrap();
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Rectilinear => "rectilinear",
+            Projection::Stereographic => "stereographic",
+            Projection::LittlePlanet => "little planet",
+            Projection::Fisheye => "fisheye",
+            Projection::Pannini => "pannini",
+        }
+    }
+
+    /// Gets the value of the `projection` uniform in `project.fs`.
+    pub fn uniform(self) -> i32 {
+        self as i32
+    }
+
+    /// Gets the usable field of view range in radians. Pannini's upper limit
+    /// depends on its compression.
+    pub fn fov_range(self, pannini_compression: f32) -> (f32, f32) {
+        let min = 10f32.to_radians();
+        match self {
+            Projection::Rectilinear => (min, 170f32.to_radians()),
+            Projection::Stereographic => (min, 300f32.to_radians()),
+            Projection::LittlePlanet => (90f32.to_radians(), 340f32.to_radians()),
+            Projection::Fisheye => (min, 2.0 * PI),
+            Projection::Pannini => {
+                // The horizontal edge reaches infinity at `cos(fov / 2) = -d`.
+                let limit = 2.0 * (-pannini_compression.min(1.0)).acos();
+                (min, (limit * 0.95).max(min))
+            }
+        }
+    }
+
+    /// Gets the field of view to switch to when the current one is out of range.
+    pub fn default_fov(self) -> f32 {
+        match self {
+            Projection::Rectilinear => 90f32.to_radians(),
+            Projection::Stereographic | Projection::Fisheye => PI,
+            Projection::LittlePlanet => 280f32.to_radians(),
+            Projection::Pannini => 150f32.to_radians(),
+        }
+    }
+
+    /// Gets the camera space ray (looking down `-z`) through screen point
+    /// `p`, where the view spans -1 to 1, or `None` where the projection
+    /// doesn't cover it. Matches `screen_ray` in `rays.glsl`.
+    pub fn camera_ray(self, p: [f32; 2], fov: f32, pannini_compression: f32) -> Option<Vec3> {
+        let [x, y] = p;
+        let r = (x * x + y * y).sqrt();
+        let polar = |theta: f32| {
+            if r < 1e-6 {
+                Vec3::new(0.0, 0.0, -1.0)
+            } else {
+                Vec3::new(x / r * theta.sin(), y / r * theta.sin(), -theta.cos())
+            }
+        };
+        match self {
+            Projection::Rectilinear => {
+                Some(Vec3::new(x * 0.5, y * 0.5, -0.5 / (fov / 2.0).tan()).normalized())
+            }
+            Projection::Stereographic | Projection::LittlePlanet => {
+                Some(polar(2.0 * (r * (fov / 4.0).tan()).atan()))
+            }
+            Projection::Fisheye => {
+                let theta = r * fov / 2.0;
+                if theta > PI {
+                    None
+                } else {
+                    Some(polar(theta))
+                }
+            }
+            Projection::Pannini => {
+                let d = pannini_compression;
+                let half_fov = fov / 2.0;
+                let edge = (d + 1.0) / (d + half_fov.cos()) * half_fov.sin();
+                let (qx, qy) = (x * edge, y * edge);
+                let k = qx * qx / ((d + 1.0) * (d + 1.0));
+                let dscr = (k * k * d * d - (k + 1.0) * (k * d * d - 1.0)).max(0.0);
+                let clon = (-k * d + dscr.sqrt()) / (k + 1.0);
+                let s = (d + 1.0) / (d + clon);
+                let lon = qx.atan2(s * clon);
+                let lat = qy.atan2(s);
+                Some(Vec3::new(
+                    lon.sin() * lat.cos(),
+                    lat.sin(),
+                    -lon.cos() * lat.cos(),
+                ))
+            }
+        }
+    }
+
+    /// Gets the screen point, where the view spans -1 to 1, that shows the
+    /// camera space direction `cam`, or `None` where the projection can't
+    /// place it. The inverse of [`camera_ray`](Self::camera_ray), matching
+    /// `project_to_screen` in `project.fs`.
+    pub fn screen_point(self, cam: Vec3, fov: f32, pannini_compression: f32) -> Option<[f32; 2]> {
+        let cam = cam.normalized();
+        let theta = (-cam.z).clamp(-1.0, 1.0).acos();
+        let r = cam.x.hypot(cam.y);
+        let dir = if r > 1e-6 {
+            [cam.x / r, cam.y / r]
+        } else {
+            [0.0, 0.0]
+        };
+        let polar = |radius: f32| Some(dir.map(|c| c * radius));
+        match self {
+            Projection::Rectilinear => {
+                if cam.z >= 0.0 {
+                    None
+                } else {
+                    let scale = 1.0 / (-cam.z * (fov / 2.0).tan());
+                    Some([cam.x * scale, cam.y * scale])
+                }
+            }
+            Projection::Stereographic | Projection::LittlePlanet => {
+                if theta > PI * 0.999 {
+                    None
+                } else {
+                    polar((theta / 2.0).tan() / (fov / 4.0).tan())
+                }
+            }
+            Projection::Fisheye => polar(theta / (fov / 2.0)),
+            Projection::Pannini => {
+                let d = pannini_compression;
+                let half_fov = fov / 2.0;
+                let edge = (d + 1.0) / (d + half_fov.cos()) * half_fov.sin();
+                let lon = cam.x.atan2(-cam.z);
+                let lat = cam.y.atan2(cam.x.hypot(cam.z));
+                if d + lon.cos() <= 0.0 {
+                    return None;
+                }
+                let s = (d + 1.0) / (d + lon.cos());
+                Some([s * lon.sin() / edge, s * lat.tan() / edge])
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINTS: [[f32; 2]; 7] = [
+        [0.0, 0.0],
+        [0.5, 0.0],
+        [0.0, -0.5],
+        [-0.3, 0.7],
+        [0.9, -0.9],
+        [-1.0, 1.0],
+        [1.0, 0.2],
+    ];
+
+    /// Checks that every point maps to a ray and back to itself.
+    fn round_trips(projection: Projection, fov_degrees: f32, pannini_compression: f32) {
+        let fov = fov_degrees.to_radians();
+        for p in POINTS {
+            let ray = projection
+                .camera_ray(p, fov, pannini_compression)
+                .unwrap_or_else(|| panic!("{:?} has no ray at {:?}", projection, p));
+            let back = projection
+                .screen_point(ray, fov, pannini_compression)
+                .unwrap_or_else(|| panic!("{:?} can't place {:?}", projection, ray));
+            assert!(
+                (back[0] - p[0]).abs() < 1e-3 && (back[1] - p[1]).abs() < 1e-3,
+                "{:?} at {}° took {:?} to {:?}",
+                projection,
+                fov_degrees,
+                p,
+                back
+            );
+        }
+    }
+
+    #[test]
+    fn rectilinear_round_trips() {
+        for fov in [30.0, 90.0, 150.0] {
+            round_trips(Projection::Rectilinear, fov, 0.0);
+        }
+    }
+
+    #[test]
+    fn stereographic_round_trips() {
+        for fov in [30.0, 90.0, 180.0, 270.0, 300.0] {
+            round_trips(Projection::Stereographic, fov, 0.0);
+            round_trips(Projection::LittlePlanet, fov, 0.0);
+        }
+    }
+
+    #[test]
+    fn fisheye_round_trips() {
+        for fov in [30.0, 90.0, 180.0, 250.0] {
+            round_trips(Projection::Fisheye, fov, 0.0);
+        }
+    }
+
+    #[test]
+    fn pannini_round_trips() {
+        for compression in [0.0, 0.5, 1.0] {
+            let (_, max) = Projection::Pannini.fov_range(compression);
+            for fov in [30.0, 90.0, 150.0, max.to_degrees()] {
+                round_trips(Projection::Pannini, fov, compression);
+            }
+        }
+    }
+}
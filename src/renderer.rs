@@ -0,0 +1,208 @@
+use gl33::gl_enumerations::*;
+use ultraviolet::Vec4;
+
+use crate::font;
+use crate::gl_safe::{
+    self, Buffer, Context, InUse, Primitive, Sampler, ShaderProgram, Texture, VertexArray,
+};
+use crate::shaders::{self, Shaders};
+use crate::stereo::{EyePass, Stereo};
+use crate::{RotImage, Source, Vertex};
+
+/// Draws views of a source image with the projection shader, then any
+/// orientation aids over them with the overlay shader.
+pub struct Renderer<'gl> {
+    gl: &'gl Context<'gl>,
+    vao: VertexArray<'gl>,
+    vbo: Buffer<'gl>,
+    program: ShaderProgram<'gl>,
+    overlay: ShaderProgram<'gl>,
+    /// Screen pixels per font pixel of the compass letters.
+    hud_scale: i32,
+    /// Font atlas for the compass letters.
+    font: Texture<'gl>,
+}
+
+/// Points a program's `rays.glsl` uniforms along one eye's view of `image`.
+fn set_rays(program: &InUse, image: &RotImage, pass: &EyePass, aspect: f32) -> Result<(), String> {
+    program.set_uniform("projection", image.projection.uniform())?;
+    program.set_uniform("fov", image.fov)?;
+    program.set_uniform("pannini_compression", image.pannini_compression)?;
+    program.set_uniform("overview", image.overview)?;
+    program.set_uniform("aspect", aspect)?;
+    program.set_uniform("orientation", image.projection_matrix(pass.yaw))?;
+    program.set_uniform("eye_rotation", image.eye_rotation_matrix(pass.yaw))
+}
+
+/// Builds a program from `project.vs` and a fragment shader that is appended
+/// to `rays.glsl`.
+fn ray_program<'gl>(
+    gl: &'gl Context<'_>,
+    shaders: &Shaders,
+    fragment: &str,
+) -> Result<ShaderProgram<'gl>, String> {
+    shaders.program(gl, "project.vs", &["rays.glsl", fragment])
+}
+
+/// Builds the overlay program and sets the uniforms that never change.
+fn overlay_program<'gl>(
+    gl: &'gl Context<'_>,
+    shaders: &Shaders,
+    hud_scale: i32,
+) -> Result<ShaderProgram<'gl>, String> {
+    let overlay = ray_program(gl, shaders, "overlay.fs")?;
+    let in_use = overlay.use_program();
+    in_use.set_uniform("font", Sampler(1))?;
+    in_use.set_uniform("hud_scale", hud_scale.max(1) as f32)?;
+    let origins = ['N', 'E', 'S', 'W'].map(|c| font::cell_origin(font::cell(c)));
+    in_use.set_uniform("cardinal_origins", origins)?;
+    Ok(overlay)
+}
+
+impl<'gl> Renderer<'gl> {
+    /// Builds the projection and overlay programs and the full screen quad
+    /// carrying the image's view rays.
+    ///
+    /// `hud_scale` sizes the compass to match the HUD text.
+    pub fn new(gl: &'gl Context<'_>, shaders: &Shaders, image: &RotImage, hud_scale: i32) -> Self {
+        let vao = VertexArray::new(gl).expect("Couldn't make a VAO");
+        vao.bind();
+
+        let vbo = Buffer::new(gl).expect("Couldn't make a VBO");
+        vbo.bind(gl_safe::BufferType::Array);
+        gl_safe::buffer_data(
+            gl_safe::BufferType::Array,
+            bytemuck::cast_slice(&image.viewrays),
+            GL_DYNAMIC_DRAW,
+        );
+
+        vao.set_layout::<Vertex>(&vbo);
+
+        let program = shaders.build_or_embedded(|shaders| ray_program(gl, shaders, "project.fs"));
+        let overlay = shaders.build_or_embedded(|shaders| overlay_program(gl, shaders, hud_scale));
+        let (atlas_width, atlas_height, pixels) = font::atlas();
+        let font = Texture::new(gl).expect("Couldn't make a texture");
+        font.load_rgba(atlas_width, atlas_height, &pixels);
+
+        Self {
+            gl,
+            font,
+            vao,
+            vbo,
+            program,
+            overlay,
+            hud_scale,
+        }
+    }
+
+    /// Rebuilds the programs after their sources changed, keeping the old
+    /// ones where the new sources don't compile.
+    pub fn reload(&mut self, gl: &'gl Context<'_>, shaders: &Shaders) {
+        shaders::swap_in(&mut self.program, ray_program(gl, shaders, "project.fs"));
+        shaders::swap_in(
+            &mut self.overlay,
+            overlay_program(gl, shaders, self.hud_scale),
+        );
+    }
+
+    /// Copies the image's rectilinear view rays to the GPU after the camera moved.
+    pub fn upload_viewrays(&self, image: &RotImage) {
+        self.vbo.bind(gl_safe::BufferType::Array);
+        gl_safe::buffer_data(
+            gl_safe::BufferType::Array,
+            bytemuck::cast_slice(&image.viewrays),
+            GL_DYNAMIC_DRAW,
+        );
+        Buffer::clear_binding(gl_safe::BufferType::Array);
+    }
+
+    /// Draws the image's view of `source` into `region`, splitting it up for
+    /// stereo output. Nothing outside `clip` is touched. Both rectangles are
+    /// `[x, y, width, height]` in pixels.
+    ///
+    /// Below an `opacity` of 1 the view is blended over what's already there
+    /// and the overlays are left out.
+    ///
+    /// Fails if the shaders lack a uniform the renderer sets.
+    pub fn draw(
+        &self,
+        image: &RotImage,
+        source: &Source,
+        texture: &Texture,
+        region: [i32; 4],
+        clip: [i32; 4],
+        opacity: f32,
+    ) -> Result<(), String> {
+        let [region_x, region_y, region_width, region_height] = region;
+        let passes = if image.overview {
+            Stereo::default().passes(region_width, region_height)
+        } else {
+            image.stereo.passes(region_width, region_height)
+        };
+        let overlays = &image.overlays;
+        let gl = self.gl;
+        self.vao.bind();
+        gl.active_texture(0);
+        texture.bind();
+        gl.active_texture(1);
+        self.font.bind();
+        gl.active_texture(0);
+
+        let program = self.program.use_program();
+        program.set_uniform("scalar", source.get_scalar())?;
+        program.set_uniform("zoom", source.zoom)?;
+        program.set_uniform("twin_view", source.twin_view)?;
+        program.set_uniform("stereo_layout", image.stereo.layout.uniform())?;
+        program.set_uniform("source_rotation", source.rotation().into_matrix())?;
+        program.set_uniform("opacity", opacity)?;
+
+        let show_overlays = overlays.any() && opacity >= 1.0;
+        if show_overlays {
+            let overlay = self.overlay.use_program();
+            overlay.set_uniform("show_grid", overlays.grid)?;
+            overlay.set_uniform("show_horizon", overlays.horizon)?;
+            overlay.set_uniform("show_compass", overlays.compass)?;
+            overlay.set_uniform("heading_offset", overlays.heading_offset().to_degrees())?;
+            overlay.set_uniform("north_known", overlays.north.is_some())?;
+        }
+
+        let result = passes.into_iter().try_for_each(|pass| {
+            let [x, y, width, height] = pass.viewport;
+            let viewport = [region_x + x, region_y + y, width, height];
+            gl.viewport(viewport);
+            let aspect = width as f32 / height.max(1) as f32;
+            let [x, y, width, height] = pass.scissor;
+            gl.scissor(Some(intersect(
+                [region_x + x, region_y + y, width, height],
+                clip,
+            )));
+            gl.color_mask(pass.color_mask);
+
+            let program = self.program.use_program();
+            program.set_uniform("eye", pass.eye)?;
+            set_rays(&program, image, &pass, aspect)?;
+            gl.draw_arrays(Primitive::TriangleFan, 0, 4);
+
+            if show_overlays {
+                let overlay = self.overlay.use_program();
+                set_rays(&overlay, image, &pass, aspect)?;
+                let [x, y, width, height] = viewport.map(|v| v as f32);
+                overlay.set_uniform("viewport", Vec4::new(x, y, width, height))?;
+                gl.draw_arrays(Primitive::TriangleFan, 0, 4);
+            }
+            Ok(())
+        });
+        gl.color_mask([true; 4]);
+        gl.scissor(None);
+        result
+    }
+}
+
+/// Gets the overlap of two `[x, y, width, height]` rectangles.
+fn intersect(a: [i32; 4], b: [i32; 4]) -> [i32; 4] {
+    let x = a[0].max(b[0]);
+    let y = a[1].max(b[1]);
+    let right = (a[0] + a[2]).min(b[0] + b[2]);
+    let top = (a[1] + a[3]).min(b[1] + b[3]);
+    [x, y, (right - x).max(0), (top - y).max(0)]
+}
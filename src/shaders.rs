@@ -0,0 +1,137 @@
+//! The GLSL sources. They're built into the binary so it runs from anywhere,
+//! or with `--shader-dir` read from a directory and reloaded whenever a file
+//! there changes, for working on them without rebuilding.
+
+use std::{
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::gl_safe::{Context, ShaderProgram};
+
+/// Every shader file, by name, with the copy built into the binary.
+const SOURCES: &[(&str, &str)] = &[
+    ("project.vs", include_str!("shd/project.vs")),
+    ("rays.glsl", include_str!("shd/rays.glsl")),
+    ("project.fs", include_str!("shd/project.fs")),
+    ("overlay.fs", include_str!("shd/overlay.fs")),
+    ("text.vs", include_str!("shd/text.vs")),
+    ("text.fs", include_str!("shd/text.fs")),
+    ("minimap.fs", include_str!("shd/minimap.fs")),
+];
+
+/// How often the shader directory is checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+pub struct Shaders {
+    /// Where to read the sources from instead of the built in copies.
+    dir: Option<PathBuf>,
+    /// When each file in `SOURCES` was last changed, as of the last check.
+    modified: Vec<Option<SystemTime>>,
+    last_poll: Instant,
+}
+
+impl Shaders {
+    /// Uses the sources built into the binary.
+    pub fn embedded() -> Self {
+        Self {
+            dir: None,
+            modified: Vec::new(),
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Reads the sources from `dir`, such as the repository's `src/shd`.
+    pub fn from_dir(dir: PathBuf) -> Self {
+        let mut shaders = Self {
+            dir: Some(dir),
+            modified: Vec::new(),
+            last_poll: Instant::now(),
+        };
+        shaders.modified = shaders.modification_times();
+        shaders
+    }
+
+    /// Gets the source of the shader file called `name`.
+    pub fn source(&self, name: &str) -> Result<String, String> {
+        match &self.dir {
+            Some(dir) => {
+                let path = dir.join(name);
+                fs::read_to_string(&path).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            None => SOURCES
+                .iter()
+                .find(|(file, _)| *file == name)
+                .map(|(_, source)| source.to_string())
+                .ok_or_else(|| format!("No built in shader called {}", name)),
+        }
+    }
+
+    /// Builds a program from a vertex shader and a fragment shader made of
+    /// the `fragment` files one after the other.
+    pub fn program<'gl>(
+        &self,
+        gl: &'gl Context<'_>,
+        vertex: &str,
+        fragment: &[&str],
+    ) -> Result<ShaderProgram<'gl>, String> {
+        let fragment_source = fragment
+            .iter()
+            .map(|name| self.source(name))
+            .collect::<Result<String, String>>()?;
+        ShaderProgram::from_vert_frag(gl, &self.source(vertex)?, &fragment_source)
+            .map_err(|e| format!("{} and {}: {}", vertex, fragment.join(" + "), e))
+    }
+
+    /// Builds a program with `build`, for start up. If the shader directory's
+    /// sources fail, says why and uses the built in ones instead.
+    pub fn build_or_embedded<'gl>(
+        &self,
+        build: impl Fn(&Shaders) -> Result<ShaderProgram<'gl>, String>,
+    ) -> ShaderProgram<'gl> {
+        let built_in = |e| panic!("The built in shaders don't build: {}", e);
+        match build(self) {
+            Ok(program) => program,
+            Err(e) if self.dir.is_some() => {
+                eprintln!("Using the built in shaders instead: {}", e);
+                build(&Self::embedded()).unwrap_or_else(built_in)
+            }
+            Err(e) => built_in(e),
+        }
+    }
+
+    /// Whether any file in the shader directory changed since the last call.
+    /// Always false for the built in sources.
+    pub fn changed(&mut self) -> bool {
+        if self.dir.is_none() || self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+        let modified = self.modification_times();
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+
+    fn modification_times(&self) -> Vec<Option<SystemTime>> {
+        let Some(dir) = &self.dir else {
+            return Vec::new();
+        };
+        SOURCES
+            .iter()
+            .map(|(name, _)| fs::metadata(dir.join(name)).and_then(|m| m.modified()).ok())
+            .collect()
+    }
+}
+
+/// Puts a rebuilt program in place of `program`, or keeps the old one and
+/// says why if it failed to build.
+pub fn swap_in<'gl>(program: &mut ShaderProgram<'gl>, rebuilt: Result<ShaderProgram<'gl>, String>) {
+    match rebuilt {
+        Ok(rebuilt) => *program = rebuilt,
+        Err(e) => eprintln!("Keeping the old shader program: {}", e),
+    }
+}
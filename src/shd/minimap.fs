@@ -0,0 +1,16 @@
+#version 330 core
+in vec2 UV;
+in vec4 Color;
+// The raw source image, drawn as is without any projection.
+uniform sampler2D source;
+// Whether to read the source or just fill with Color.
+uniform bool textured;
+out vec4 FragColor;
+
+void main()
+{
+  if (textured)
+    FragColor = Color * vec4(texture(source, UV).rgb, 1.0);
+  else
+    FragColor = Color;
+}
@@ -0,0 +1,153 @@
+// Appended to rays.glsl.
+// Orientation aids drawn over the projected image.
+uniform bool show_grid;
+uniform bool show_horizon;
+uniform bool show_compass;
+// Degrees added to a direction's longitude to get its compass heading.
+uniform float heading_offset;
+// Whether heading_offset comes from the image's recorded north.
+uniform bool north_known;
+// This pass's viewport as x, y, width and height in pixels.
+uniform vec4 viewport;
+// Screen pixels per font pixel.
+uniform float hud_scale;
+uniform sampler2D font;
+// Top left texel of the N, E, S and W glyphs in the font atlas.
+uniform ivec2 cardinal_origins[4];
+out vec4 FragColor;
+
+#define GRID_SPACING 15.0
+#define GLYPH_SIZE 8.0
+
+const vec4 GRID_COLOR = vec4(1.0, 1.0, 1.0, 0.35);
+const vec4 HORIZON_COLOR = vec4(0.0, 1.0, 1.0, 0.9);
+const vec4 STRIP_COLOR = vec4(0.0, 0.0, 0.0, 0.5);
+const vec4 TICK_COLOR = vec4(1.0, 1.0, 1.0, 0.9);
+const vec4 NORTH_COLOR = vec4(1.0, 0.2, 0.2, 1.0);
+const vec4 MARK_COLOR = vec4(1.0, 0.85, 0.0, 1.0);
+
+// Longitude and latitude of a source space direction in degrees, matching
+// overview_ray.
+vec2 lon_lat(vec3 R)
+{
+  return degrees(vec2(atan(-R.z, -R.x), asin(clamp(R.y, -1.0, 1.0))));
+}
+
+// Wraps an angle difference in degrees into -180 to 180.
+float wrap(float degrees)
+{
+  return degrees - 360.0 * round(degrees / 360.0);
+}
+
+// Degrees from `x` to the nearest multiple of `spacing`.
+float to_multiple(float x, float spacing)
+{
+  return abs(wrap(x / spacing * 360.0)) / 360.0 * spacing;
+}
+
+// Coverage of a one pixel line `distance` away, where a pixel spans `width`.
+float line(float distance, float width)
+{
+  return 1.0 - smoothstep(0.5, 1.5, distance / max(width, 1e-6));
+}
+
+// Draws `top` over `base`, both with straight alpha.
+vec4 over(vec4 base, vec4 top)
+{
+  float a = top.a + base.a * (1.0 - top.a);
+  if (a <= 0.0)
+    return vec4(0.0);
+  return vec4((top.rgb * top.a + base.rgb * base.a * (1.0 - top.a)) / a, a);
+}
+
+// Grid lines and horizon at longitude and latitude `ll`, where a pixel spans
+// `lon_px` and `lat_px` degrees.
+vec4 grid_and_horizon(vec2 ll, float lon_px, float lat_px)
+{
+  vec4 color = vec4(0.0);
+  if (show_grid) {
+    float lat_line = line(to_multiple(ll.y, GRID_SPACING), lat_px);
+    // Meridians crowd together at the poles; fade them out there.
+    float lon_line = line(to_multiple(ll.x, GRID_SPACING), lon_px)
+      * (1.0 - smoothstep(GRID_SPACING / 8.0, GRID_SPACING / 4.0, lon_px));
+    color = over(color, GRID_COLOR * vec4(1.0, 1.0, 1.0, max(lat_line, lon_line)));
+  }
+  if (show_horizon)
+    color = over(color, HORIZON_COLOR * vec4(1.0, 1.0, 1.0, line(abs(ll.y) / 1.5, lat_px)));
+  return color;
+}
+
+// Samples one of the N, E, S and W glyphs `offset` font pixels from its top
+// left corner.
+float cardinal_glyph(int index, vec2 offset)
+{
+  if (any(lessThan(offset, vec2(0.0))) || any(greaterThanEqual(offset, vec2(GLYPH_SIZE))))
+    return 0.0;
+  return texelFetch(font, cardinal_origins[index] + ivec2(offset), 0).a;
+}
+
+// A strip across the top of the view with a tick every 10 degrees of
+// heading along the middle row of the screen. `heading` is this column's and
+// a pixel spans `px` degrees of it.
+vec4 compass(float heading, float px, bool covered)
+{
+  // Letters sit in the top of the band and ticks rise from its bottom.
+  float band = (GLYPH_SIZE + 8.0) * hud_scale;
+  float from_top = viewport.y + viewport.w - gl_FragCoord.y;
+  if (from_top >= band)
+    return vec4(0.0);
+  vec4 color = STRIP_COLOR;
+  if (!covered)
+    return color;
+  float rise = (band - from_top) / band;
+  float tick = 0.0;
+  if (rise < 0.18)
+    tick = line(to_multiple(heading, 10.0), px);
+  if (rise < 0.3)
+    tick = max(tick, line(to_multiple(heading, 30.0), px));
+  if (rise < 0.4)
+    tick = max(tick, line(to_multiple(heading, 90.0), px));
+  // The zero tick is north, or zero yaw when north is unknown.
+  vec4 tick_color = TICK_COLOR;
+  if (to_multiple(heading, 360.0) < 5.0)
+    tick_color = north_known ? NORTH_COLOR : MARK_COLOR;
+  color = over(color, tick_color * vec4(1.0, 1.0, 1.0, tick));
+
+  if (north_known) {
+    for (int i = 0; i < 4; i++) {
+      // Pixels from this heading to the cardinal point's tick.
+      float offset = wrap(heading - 90.0 * float(i)) / max(px, 1e-6);
+      vec2 glyph = vec2(offset, from_top - hud_scale) / hud_scale + vec2(GLYPH_SIZE / 2.0, 0.0);
+      float coverage = cardinal_glyph(i, floor(glyph));
+      color = over(color, (i == 0 ? NORTH_COLOR : TICK_COLOR) * vec4(1.0, 1.0, 1.0, coverage));
+    }
+  }
+
+  // Marks the current heading.
+  float from_centre = abs(Screen.x) * viewport.z / 2.0;
+  color = over(color, MARK_COLOR * vec4(1.0, 1.0, 1.0, line(from_centre, hud_scale)));
+  return color;
+}
+
+void main()
+{
+  vec3 view = overview ? overview_ray() : view_ray();
+  bool covered = dot(view, view) > 0.0;
+  vec2 ll = lon_lat(covered ? normalize(view) : vec3(-1.0, 0.0, 0.0));
+  vec3 centre = screen_ray(vec2(Screen.x, 0.0));
+  bool centre_covered = dot(centre, centre) > 0.0;
+  float heading = lon_lat(centre_covered ? normalize(centre) : vec3(-1.0, 0.0, 0.0)).x
+    + heading_offset;
+  // Degrees per pixel, ignoring the jump where longitude wraps. Derivatives
+  // are only defined outside branches, so take them all up front.
+  float lon_px = abs(wrap(dFdx(ll.x))) + abs(wrap(dFdy(ll.x)));
+  float lat_px = fwidth(ll.y);
+  float heading_px = abs(wrap(dFdx(heading)));
+
+  vec4 color = vec4(0.0);
+  if (covered)
+    color = grid_and_horizon(ll, lon_px, lat_px);
+  if (show_compass && !overview)
+    color = over(color, compass(heading, heading_px, centre_covered));
+  FragColor = color;
+}
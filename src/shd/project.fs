@@ -1,16 +1,35 @@
-#version 330 core
+// Appended to rays.glsl.
 #define M_2xSQRT2 2.8284271247461900976033774484194
 //precision highp float;
-in vec3 Ray;
 uniform float scalar;
 uniform sampler2D sample_projection;
 //uniform bool twin_view; 
 uniform float zoom; //= 0.9280;
 uniform bool twin_view;
+// How a stereo source packs its eyes: 0 mono, 1 top/bottom, 2 left/right.
+uniform int stereo_layout;
+// Which eye to sample from a stereo source, 0 left and 1 right.
+uniform int eye;
+// Turns source space directions before sampling, for a scene fading out
+// after the camera turned to enter the next one.
+uniform mat3 source_rotation;
+// Less than 1 while a scene fades out over the next.
+uniform float opacity;
 
-void main()
+// Moves a texture coordinate into the current eye's part of a stereo source.
+// Coordinates may be a whole texture away from 0 to 1, so wrap them first.
+vec2 stereo_uv(vec2 uv)
+{
+  if (stereo_layout == 1)
+    return vec2(uv.x, fract(uv.y) * 0.5 + 0.5 * float(eye));
+  if (stereo_layout == 2)
+    return vec2(fract(uv.x) * 0.5 + 0.5 * float(eye), uv.y);
+  return uv;
+}
+
+// Looks up the colour the source image records for direction R.
+vec3 sample_source(vec3 R)
 {
-	vec3 R = normalize(Ray);
   vec2 iRay;
   if(twin_view)
     iRay = R.xy / (M_2xSQRT2 * sqrt(abs(R.z) + 1.0));
@@ -27,12 +46,64 @@ void main()
     uv.x /= 2;
   }
   if(!twin_view && length(iRay_scaled) >= 0.5 && scalar > 1.0)
-    gl_FragColor = vec4(0.0,0.0,0.0,1.0);
+    return vec3(0.0);
   else if(twin_view && R.z < 0) {
     uv.x = 0.5 - uv.x;
     uv.x += 0.5;
-    gl_FragColor = vec4(texture2D(sample_projection, uv).rgb, 1.0);
-  } else {
-    gl_FragColor = vec4(texture2D(sample_projection, uv).rgb, 1.0);
-	}
+  }
+  return texture2D(sample_projection, stereo_uv(uv)).rgb;
+}
+
+// Projects a camera space direction onto the screen of the current
+// projection, where the view spans -1 to 1. Directions the projection can't
+// show land far outside that range.
+vec2 project_to_screen(vec3 cam)
+{
+  vec2 outside = vec2(1000.0);
+  float theta = acos(clamp(-cam.z, -1.0, 1.0));
+  vec2 dir = length(cam.xy) > 1e-6 ? normalize(cam.xy) : vec2(0.0);
+  if (projection == 0) {
+    if (cam.z >= 0.0)
+      return outside;
+    return cam.xy / -cam.z / tan(fov / 2.0);
+  } else if (projection == 1 || projection == 2) {
+    if (theta > M_PI * 0.999)
+      return outside;
+    return dir * tan(theta / 2.0) / tan(fov / 4.0);
+  } else if (projection == 3) {
+    return dir * theta / (fov / 2.0);
+  }
+  float d = pannini_compression;
+  float half_fov = fov / 2.0;
+  float edge = (d + 1.0) / (d + cos(half_fov)) * sin(half_fov);
+  float lon = atan(cam.x, -cam.z);
+  float lat = atan(cam.y, length(cam.xz));
+  if (d + cos(lon) <= 0.0)
+    return outside;
+  float S = (d + 1.0) / (d + cos(lon));
+  return vec2(S * sin(lon), S * tan(lat)) / edge;
+}
+
+// Whether a source space direction lies on the edge of the current view.
+bool on_footprint_edge(vec3 R)
+{
+  vec3 cam = transpose(orientation) * R;
+  vec2 s = project_to_screen(cam);
+  float m = max(abs(s.x), abs(s.y));
+  float width = fwidth(m);
+  return m <= 1.0 && 1.0 - m < width * 1.5 && width < 0.5;
+}
+
+void main()
+{
+  vec3 view = overview ? overview_ray() : view_ray();
+  if (dot(view, view) == 0.0) {
+    gl_FragColor = vec4(0.0, 0.0, 0.0, opacity);
+    return;
+  }
+  vec3 R = normalize(view);
+  vec3 color = sample_source(source_rotation * R);
+  if (overview && on_footprint_edge(R))
+    color = vec3(1.0, 0.85, 0.0);
+  gl_FragColor = vec4(color, opacity);
 }
@@ -2,9 +2,11 @@
 layout (location=0) in vec2 pos;
 layout (location=1) in vec3 rayvtx;
 out vec3 Ray;
+out vec2 Screen;
 
 void main()
 {
 	Ray = rayvtx;
+	Screen = pos;
 	gl_Position = vec4(pos, 0.0, 1.0);
 }
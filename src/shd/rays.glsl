@@ -0,0 +1,85 @@
+#version 330 core
+// Shared by every pass drawn with the view rays; the fragment shaders that
+// use it are appended to it.
+#define M_PI 3.1415926535897932384626433832795
+in vec3 Ray;
+in vec2 Screen;
+// 0 rectilinear, 1 stereographic, 2 little planet, 3 fisheye, 4 pannini
+uniform int projection;
+uniform mat3 orientation;
+uniform float fov;
+uniform float pannini_compression;
+// Shows the whole sphere with the current view outlined instead of the view.
+uniform bool overview;
+// Viewport width over height.
+uniform float aspect;
+// Turns the interpolated rectilinear rays towards this eye's view.
+uniform mat3 eye_rotation;
+
+// Camera space ray (looking down -z) leaving at `theta` from the view axis,
+// in the screen direction of `p`.
+vec3 polar_ray(vec2 p, float theta)
+{
+  float r = length(p);
+  if (r < 1e-6)
+    return vec3(0.0, 0.0, -1.0);
+  return vec3(p / r * sin(theta), -cos(theta));
+}
+
+vec3 pannini_ray(vec2 p)
+{
+  float d = pannini_compression;
+  float half_fov = fov / 2.0;
+  // Scale the screen so its edge lands on the projected half field of view.
+  float edge = (d + 1.0) / (d + cos(half_fov)) * sin(half_fov);
+  vec2 q = p * edge;
+  float k = q.x * q.x / ((d + 1.0) * (d + 1.0));
+  float dscr = max(k * k * d * d - (k + 1.0) * (k * d * d - 1.0), 0.0);
+  float clon = (-k * d + sqrt(dscr)) / (k + 1.0);
+  float S = (d + 1.0) / (d + clon);
+  float lon = atan(q.x, S * clon);
+  float lat = atan(q.y, S);
+  return vec3(sin(lon) * cos(lat), sin(lat), -cos(lon) * cos(lat));
+}
+
+// Gets the source space ray through screen point `p`, or a zero vector when
+// the projection doesn't cover it. The rectilinear case is computed here
+// rather than interpolated from the vertices.
+vec3 screen_ray(vec2 p)
+{
+  vec3 ray;
+  if (projection == 0) {
+    ray = vec3(p * 0.5, -0.5 / tan(fov / 2.0));
+  } else if (projection == 1 || projection == 2) {
+    ray = polar_ray(p, 2.0 * atan(length(p) * tan(fov / 4.0)));
+  } else if (projection == 3) {
+    float theta = length(p) * fov / 2.0;
+    if (theta > M_PI)
+      return vec3(0.0);
+    ray = polar_ray(p, theta);
+  } else {
+    ray = pannini_ray(p);
+  }
+  return orientation * ray;
+}
+
+// Gets the source space ray for this pixel, or a zero vector when the
+// projection doesn't cover it.
+vec3 view_ray()
+{
+  if (projection == 0)
+    return eye_rotation * Ray;
+  return screen_ray(Screen);
+}
+
+// Equirectangular ray for the overview, letterboxed to keep its 2:1 shape.
+vec3 overview_ray()
+{
+  vec2 fit = vec2(max(aspect / 2.0, 1.0), max(2.0 / aspect, 1.0));
+  float lon = Screen.x * fit.x * M_PI;
+  float lat = Screen.y * fit.y * M_PI / 2.0;
+  if (abs(lon) > M_PI || abs(lat) > M_PI / 2.0)
+    return vec3(0.0);
+  return vec3(-cos(lat) * cos(lon), sin(lat), -cos(lat) * sin(lon));
+}
+
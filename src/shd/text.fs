@@ -0,0 +1,11 @@
+#version 330 core
+in vec2 UV;
+in vec4 Color;
+// White glyphs whose alpha is their coverage.
+uniform sampler2D font;
+out vec4 FragColor;
+
+void main()
+{
+  FragColor = Color * texture(font, UV);
+}
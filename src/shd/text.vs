@@ -0,0 +1,16 @@
+#version 330 core
+layout (location=0) in vec2 pos;
+layout (location=1) in vec2 uv;
+layout (location=2) in vec4 color;
+// Window size in pixels; pos is measured from the top left.
+uniform vec2 screen_size;
+out vec2 UV;
+out vec4 Color;
+
+void main()
+{
+	UV = uv;
+	Color = color;
+	vec2 ndc = pos / screen_size * 2.0 - 1.0;
+	gl_Position = vec4(ndc.x, -ndc.y, 0.0, 1.0);
+}
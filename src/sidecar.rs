@@ -0,0 +1,35 @@
+//! JSON files kept next to an image and named after it, e.g.
+//! `bridge.jpg.annotations.json`.
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Gets the path of an image's `kind` sidecar.
+pub fn path(image: &Path, kind: &str) -> PathBuf {
+    let mut name = image.file_name().unwrap_or_default().to_owned();
+    name.push(format!(".{}.json", kind));
+    image.with_file_name(name)
+}
+
+/// Reads a sidecar. A missing sidecar gives the default; a broken one is
+/// reported and also gives the default.
+pub fn load<T: DeserializeOwned + Default>(path: &Path) -> T {
+    match fs::read_to_string(path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            println!("Could not read {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(_) => T::default(),
+    }
+}
+
+/// Writes a sidecar, reporting any failure.
+pub fn save<T: Serialize>(path: &Path, value: &T) {
+    let text = serde_json::to_string_pretty(value).expect("Sidecars always serialize");
+    if let Err(e) = fs::write(path, text) {
+        println!("Could not write {}: {}", path.display(), e);
+    }
+}
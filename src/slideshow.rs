@@ -0,0 +1,64 @@
+//! Steps through the scenes by itself, for presentations.
+
+use serde::Deserialize;
+use std::time::Instant;
+
+use crate::config::SlideshowConfig;
+
+/// How one scene gives way to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transition {
+    /// Switch straight away.
+    Cut,
+    /// Fade the old scene out over the new one.
+    #[default]
+    Crossfade,
+    /// Zoom into the old scene while fading it out, and out of the new one.
+    ZoomThrough,
+}
+
+impl Transition {
+    /// Parses the `--transition` argument.
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "cut" => Some(Transition::Cut),
+            "crossfade" | "fade" => Some(Transition::Crossfade),
+            "zoom" | "zoom-through" => Some(Transition::ZoomThrough),
+            _ => None,
+        }
+    }
+}
+
+/// Timing for moving on to the next scene.
+#[derive(Debug, Clone, Copy)]
+pub struct Slideshow {
+    /// Seconds each scene is shown for.
+    pub dwell: f32,
+    pub transition: Transition,
+    /// Seconds the transition takes.
+    pub transition_seconds: f32,
+    /// When the current scene came up.
+    since: Instant,
+}
+
+impl Slideshow {
+    pub fn new(config: &SlideshowConfig) -> Self {
+        Self {
+            dwell: config.dwell.max(0.0),
+            transition: config.transition,
+            transition_seconds: config.transition_seconds.max(0.0),
+            since: Instant::now(),
+        }
+    }
+
+    /// Whether the current scene has been shown for long enough.
+    pub fn due(&self) -> bool {
+        self.since.elapsed().as_secs_f32() >= self.dwell
+    }
+
+    /// Starts timing a newly shown scene.
+    pub fn restart(&mut self) {
+        self.since = Instant::now();
+    }
+}
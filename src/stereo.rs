@@ -0,0 +1,158 @@
+use serde::Deserialize;
+
+/// How a stereo source packs its two eyes into one image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StereoLayout {
+    #[default]
+    Mono,
+    /// Left eye above the right eye (over-under).
+    TopBottom,
+    /// Left eye beside the right eye.
+    LeftRight,
+}
+
+impl StereoLayout {
+    /// Parses the `--stereo` command line value.
+    pub fn from_arg(arg: &str) -> Option<Self> {
+        match arg {
+            "mono" => Some(StereoLayout::Mono),
+            "tb" | "top-bottom" => Some(StereoLayout::TopBottom),
+            "lr" | "left-right" => Some(StereoLayout::LeftRight),
+            _ => None,
+        }
+    }
+
+    /// Gets the value of the `stereo_layout` uniform in `project.fs`.
+    pub fn uniform(self) -> i32 {
+        self as i32
+    }
+
+    /// Moves a texture coordinate into one eye's part of the source, like
+    /// `stereo_uv` in `project.fs`. Coordinates are wrapped into 0 to 1.
+    pub fn eye_uv(self, uv: [f32; 2], eye: i32) -> [f32; 2] {
+        let [u, v] = uv.map(|c| c.rem_euclid(1.0));
+        let offset = 0.5 * eye as f32;
+        match self {
+            StereoLayout::Mono => [u, v],
+            StereoLayout::TopBottom => [u, v * 0.5 + offset],
+            StereoLayout::LeftRight => [u * 0.5 + offset, v],
+        }
+    }
+}
+
+/// How the two eyes are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StereoOutput {
+    /// Only the left eye.
+    Mono,
+    /// Left eye on the left half of the window, for cardboard style viewers.
+    SideBySide,
+    /// Left eye in red and right eye in cyan, for anaglyph glasses.
+    Anaglyph,
+}
+
+impl StereoOutput {
+    pub fn next(self) -> Self {
+        match self {
+            StereoOutput::Mono => StereoOutput::SideBySide,
+            StereoOutput::SideBySide => StereoOutput::Anaglyph,
+            StereoOutput::Anaglyph => StereoOutput::Mono,
+        }
+    }
+}
+
+/// One draw of the projection shader.
+pub struct EyePass {
+    /// Which half of a stereo source to sample, `0` for left and `1` for right.
+    pub eye: i32,
+    /// Viewport as `[x, y, width, height]`.
+    pub viewport: [i32; 4],
+    /// Pixels outside this `[x, y, width, height]` rectangle are left alone.
+    pub scissor: [i32; 4],
+    /// Which of red, green, blue and alpha the pass writes.
+    pub color_mask: [bool; 4],
+    /// Extra yaw for this eye in radians, positive turns left.
+    pub yaw: f32,
+}
+
+#[derive(Clone)]
+pub struct Stereo {
+    pub layout: StereoLayout,
+    pub output: StereoOutput,
+    /// Distance between the centres of the two side by side views, as a
+    /// fraction of the window width. `0.5` splits the window evenly.
+    pub ipd: f32,
+    /// Radians each eye turns in towards the other, moving the depth that
+    /// appears at the screen.
+    pub convergence: f32,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            layout: StereoLayout::Mono,
+            output: StereoOutput::Mono,
+            ipd: 0.5,
+            convergence: 0.0,
+        }
+    }
+}
+
+impl Stereo {
+    /// Gets the passes needed to draw a `width` by `height` viewport.
+    pub fn passes(&self, width: i32, height: i32) -> Vec<EyePass> {
+        let full = [0, 0, width, height];
+        let half_convergence = self.convergence / 2.0;
+        match self.output {
+            StereoOutput::Mono => vec![EyePass {
+                eye: 0,
+                viewport: full,
+                scissor: full,
+                color_mask: [true; 4],
+                yaw: 0.0,
+            }],
+            StereoOutput::SideBySide => {
+                let half = width / 2;
+                (0..2)
+                    .map(|eye| {
+                        let side = if eye == 0 { -1.0 } else { 1.0 };
+                        let centre = width as f32 * (0.5 + side * self.ipd / 2.0);
+                        EyePass {
+                            eye,
+                            viewport: [(centre - half as f32 / 2.0) as i32, 0, half, height],
+                            scissor: [eye * half, 0, half, height],
+                            color_mask: [true; 4],
+                            yaw: side * half_convergence,
+                        }
+                    })
+                    .collect()
+            }
+            StereoOutput::Anaglyph => vec![
+                EyePass {
+                    eye: 0,
+                    viewport: full,
+                    scissor: full,
+                    color_mask: [true, false, false, true],
+                    yaw: -half_convergence,
+                },
+                EyePass {
+                    eye: 1,
+                    viewport: full,
+                    scissor: full,
+                    color_mask: [false, true, true, true],
+                    yaw: half_convergence,
+                },
+            ],
+        }
+    }
+
+    pub fn change_ipd(&mut self, delta: f32) {
+        self.ipd = (self.ipd + delta).clamp(0.2, 0.8);
+    }
+
+    pub fn change_convergence(&mut self, delta: f32) {
+        let limit = 5f32.to_radians();
+        self.convergence = (self.convergence + delta).clamp(-limit, limit);
+    }
+}
@@ -0,0 +1,182 @@
+//! Panoramas linked into a walkable tour by hotspots.
+//!
+//! A tour is a TOML file listing scenes. Image paths are relative to the
+//! tour file, and angles are in degrees:
+//!
+//! ```toml
+//! start = "hall"
+//! crossfade = 0.8
+//!
+//! [[scenes]]
+//! name = "hall"
+//! image = "hall.jpg"
+//! view = "360"
+//! projection = "stereographic"
+//! fov = 120
+//! yaw = 90
+//! pitch = -10
+//!
+//! [[scenes.hotspots]]
+//! to = "kitchen"
+//! yaw = 45
+//! pitch = -5
+//! yaw_offset = 180
+//! ```
+
+use serde::Deserialize;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use ultraviolet::Vec3;
+
+use crate::annotations::Direction;
+use crate::projection::Projection;
+use crate::Source;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Tour {
+    /// Name of the first scene shown; the first listed when missing.
+    #[serde(default)]
+    pub start: Option<String>,
+    /// Seconds a hotspot takes to fade from one scene into the next.
+    #[serde(default = "default_crossfade")]
+    pub crossfade: f32,
+    pub scenes: Vec<Scene>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    pub name: String,
+    pub image: PathBuf,
+    /// View type, as given on the command line: the source's field of view in
+    /// degrees, or `twin`.
+    #[serde(default = "default_view")]
+    pub view: String,
+    pub projection: Option<Projection>,
+    pub fov: Option<f32>,
+    /// Where the camera looks when the scene is entered other than through a
+    /// hotspot.
+    pub yaw: Option<f32>,
+    pub pitch: Option<f32>,
+    #[serde(default)]
+    pub hotspots: Vec<Hotspot>,
+}
+
+/// A spot in a scene that leads to another scene when clicked.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Hotspot {
+    /// Name of the scene it leads to.
+    pub to: String,
+    #[serde(flatten)]
+    pub at: Direction,
+    /// Shown next to the hotspot instead of the scene name.
+    pub label: Option<String>,
+    /// Added to the camera's yaw on arrival, for scenes photographed facing
+    /// different ways. The camera otherwise keeps looking the same way.
+    #[serde(default)]
+    pub yaw_offset: f32,
+}
+
+fn default_crossfade() -> f32 {
+    0.8
+}
+
+fn default_view() -> String {
+    "360".to_string()
+}
+
+impl Tour {
+    /// Reads a tour file, resolving image paths and checking that every
+    /// scene's view type is valid and that every scene a hotspot or `start`
+    /// names exists.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+        let text = fs::read_to_string(path).map_err(|e| error(&e))?;
+        let mut tour: Tour = toml::from_str(&text).map_err(|e| error(&e))?;
+        if tour.scenes.is_empty() {
+            return Err(error(&"a tour needs at least one scene"));
+        }
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for scene in &mut tour.scenes {
+            scene.image = dir.join(&scene.image);
+            Source::from_view_arg(&scene.view)
+                .map_err(|e| error(&format!("scene {:?}: {}", scene.name, e)))?;
+        }
+        let targets = tour
+            .scenes
+            .iter()
+            .flat_map(|scene| &scene.hotspots)
+            .map(|h| &h.to);
+        for name in tour.start.iter().chain(targets) {
+            if tour.scene_index(name).is_none() {
+                return Err(error(&format!("no scene is named {:?}", name)));
+            }
+        }
+        Ok(tour)
+    }
+
+    /// Makes a tour without hotspots out of images given on the command line,
+    /// all with the same view type.
+    pub fn from_images(images: &[String], view: &str) -> Self {
+        Self {
+            start: None,
+            crossfade: 0.0,
+            scenes: images
+                .iter()
+                .map(|image| Scene {
+                    name: image.clone(),
+                    image: PathBuf::from(image),
+                    view: view.to_string(),
+                    projection: None,
+                    fov: None,
+                    yaw: None,
+                    pitch: None,
+                    hotspots: Vec::new(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn scene_index(&self, name: &str) -> Option<usize> {
+        self.scenes.iter().position(|scene| scene.name == name)
+    }
+
+    pub fn start_index(&self) -> usize {
+        self.start
+            .as_deref()
+            .and_then(|name| self.scene_index(name))
+            .unwrap_or(0)
+    }
+}
+
+impl Hotspot {
+    /// Gets the text drawn beside the hotspot.
+    pub fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.to)
+    }
+}
+
+impl Scene {
+    /// Finds the hotspot closest to `direction`, if any is within `max_angle`
+    /// radians.
+    pub fn hotspot_at(&self, direction: Vec3, max_angle: f32) -> Option<usize> {
+        self.hotspots
+            .iter()
+            .map(|hotspot| {
+                hotspot
+                    .at
+                    .to_vector()
+                    .dot(direction)
+                    .clamp(-1.0, 1.0)
+                    .acos()
+            })
+            .enumerate()
+            .filter(|&(_, angle)| angle <= max_angle)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+}